@@ -0,0 +1,306 @@
+//! Measures of how strongly two tags are associated, computed from their
+//! post counts and co-count. See [`crate::nmpi::get_association_matrix`].
+
+use crate::error::{Error, Result};
+use crate::tags::Tags;
+use serde::{Deserialize, Serialize};
+
+/// Counts of a pair of tags `a` and `b` over a corpus of posts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PairCounts {
+    /// Posts tagged with both.
+    pub c_ab: u32,
+    pub c_a: u32,
+    pub c_b: u32,
+    pub n_posts: u32,
+}
+
+impl PairCounts {
+    /// Checks that `n_posts` fits in the counts, as the counts of each tag
+    /// and pair then do.
+    pub fn checked_n_posts(n_posts: usize) -> Result<u32> {
+        u32::try_from(n_posts).map_err(|_| Error::TooManyPosts(n_posts))
+    }
+
+    /// The 2x2 contingency table `[[ab, a¬b], [¬ab, ¬a¬b]]`.
+    fn table(&self) -> [[f64; 2]; 2] {
+        let (ab, a, b, n) = (self.c_ab as f64, self.c_a as f64, self.c_b as f64, self.n_posts as f64);
+        [[ab, a - ab], [b - ab, n - a - b + ab]]
+    }
+
+    /// Whether `a` and `b` co-occur more often than if they were independent.
+    fn attracted(&self) -> bool {
+        self.c_ab as f64 * self.n_posts as f64 > self.c_a as f64 * self.c_b as f64
+    }
+}
+
+/// A score for a pair of tags. Scores of zero are not stored, and higher
+/// means more related.
+pub trait AssociationMeasure {
+    fn score(&self, counts: PairCounts) -> f32;
+
+    /// Whether `score` is the same with `a` and `b` swapped. Asymmetric
+    /// measures score `a` as the row and `b` as the column.
+    fn is_symmetric(&self) -> bool {
+        true
+    }
+}
+
+/// Pointwise mutual information, `log2(P(a, b) / (P(a) P(b)))`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pmi;
+
+/// [`Pmi`] with negative scores clamped to zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ppmi;
+
+/// [`Pmi`] divided by `-log2(P(a, b))`, from -1 to 1. Negative scores are
+/// clamped to zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Npmi;
+
+/// Support thresholds and smoothing for [`SmoothedNpmi`]. The default
+/// applies none, giving plain [`Npmi`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Smoothing {
+    /// Pairs with a tag on fewer posts than this score zero.
+    pub min_tag_count: u32,
+    /// Pairs sharing fewer posts than this score zero.
+    pub min_pair_count: u32,
+    /// Pseudo-count added to each cell of a pair's contingency table, so a
+    /// pair seen once together and never apart no longer scores 1.
+    pub additive: f32,
+    /// Power the marginal of the column tag is raised to, from 0 to 1, as
+    /// word2vec smooths its context distribution with 0.75. Below 1 it
+    /// raises the probability of rare tags, so they no longer make every
+    /// pair they are in look strongly associated, and scores are no longer
+    /// symmetric. It lowers that of frequent tags too, so scores are
+    /// clamped to 1.
+    pub alpha: f32,
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing {
+            min_tag_count: 0,
+            min_pair_count: 0,
+            additive: 0.0,
+            alpha: 1.0,
+        }
+    }
+}
+
+/// [`Npmi`] of smoothed counts, see [`Smoothing`]. The smoothed context
+/// marginals are rescaled to keep their total, which depends on the whole
+/// vocabulary.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothedNpmi {
+    smoothing: Smoothing,
+    /// Turns a smoothed count raised to `alpha` back into a count, exactly 1
+    /// when `alpha` is.
+    context_scale: f64,
+}
+
+impl SmoothedNpmi {
+    pub fn new(tags: &Tags, smoothing: Smoothing) -> SmoothedNpmi {
+        let k = smoothing.additive as f64;
+        let alpha = smoothing.alpha as f64;
+        let (mut total, mut total_alpha) = (0.0, 0.0);
+        for idx in 0..tags.len() {
+            let c = tags.get_count_idx(idx).unwrap_or(0) as f64 + 2.0 * k;
+            total += c;
+            total_alpha += c.powf(alpha);
+        }
+        let context_scale = if total_alpha > 0.0 { total / total_alpha } else { 1.0 };
+
+        SmoothedNpmi {
+            smoothing,
+            context_scale,
+        }
+    }
+}
+
+/// Posts with both over posts with either.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Jaccard;
+
+/// Twice the posts with both over the sum of the posts with each.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dice;
+
+/// `P(a, b) / (P(a) P(b))`, [`Pmi`] without the logarithm.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lift;
+
+/// `P(b | a)`, how often a post tagged `a` is also tagged `b`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConditionalProbability;
+
+/// Dunning's log-likelihood ratio G² of the contingency table. Only
+/// attraction is scored; pairs that co-occur less than chance score zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogLikelihoodRatio;
+
+/// Pearson's chi-square statistic of the contingency table. Only attraction
+/// is scored; pairs that co-occur less than chance score zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChiSquare;
+
+/// `log2(p_ab / (p_a p_b)) / -log2(p_ab)` clamped to `[0, 1]`, and zero if
+/// undefined.
+fn npmi(p_ab: f64, p_a: f64, p_b: f64) -> f32 {
+    let npmi = (p_ab / (p_a * p_b)).log2() / -p_ab.log2();
+    // pairs on every post give 0 / 0
+    if npmi.is_nan() { 0.0 } else { npmi.clamp(0.0, 1.0) as f32 }
+}
+
+fn pmi(counts: PairCounts) -> f32 {
+    let p_a = counts.c_a as f32 / counts.n_posts as f32;
+    let p_b = counts.c_b as f32 / counts.n_posts as f32;
+    let p_ab = counts.c_ab as f32 / counts.n_posts as f32;
+
+    p_ab.log2() - p_a.log2() - p_b.log2()
+}
+
+impl AssociationMeasure for Pmi {
+    fn score(&self, counts: PairCounts) -> f32 {
+        pmi(counts)
+    }
+}
+
+impl AssociationMeasure for Ppmi {
+    fn score(&self, counts: PairCounts) -> f32 {
+        pmi(counts).max(0.0)
+    }
+}
+
+impl AssociationMeasure for Npmi {
+    fn score(&self, counts: PairCounts) -> f32 {
+        let n = counts.n_posts as f64;
+        npmi(counts.c_ab as f64 / n, counts.c_a as f64 / n, counts.c_b as f64 / n)
+    }
+}
+
+impl AssociationMeasure for SmoothedNpmi {
+    fn score(&self, counts: PairCounts) -> f32 {
+        let smoothing = &self.smoothing;
+        if counts.c_a.min(counts.c_b) < smoothing.min_tag_count || counts.c_ab < smoothing.min_pair_count {
+            return 0.0;
+        }
+        let k = smoothing.additive as f64;
+        let alpha = smoothing.alpha as f64;
+        let n = counts.n_posts as f64 + 4.0 * k;
+
+        let p_ab = (counts.c_ab as f64 + k) / n;
+        let p_a = (counts.c_a as f64 + 2.0 * k) / n;
+        let p_b = (counts.c_b as f64 + 2.0 * k).powf(alpha) * self.context_scale / n;
+
+        npmi(p_ab, p_a, p_b)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.smoothing.alpha == 1.0
+    }
+}
+
+impl AssociationMeasure for Jaccard {
+    fn score(&self, counts: PairCounts) -> f32 {
+        counts.c_ab as f32 / (counts.c_a as f32 + counts.c_b as f32 - counts.c_ab as f32)
+    }
+}
+
+impl AssociationMeasure for Dice {
+    fn score(&self, counts: PairCounts) -> f32 {
+        2.0 * counts.c_ab as f32 / (counts.c_a as f32 + counts.c_b as f32)
+    }
+}
+
+impl AssociationMeasure for Lift {
+    fn score(&self, counts: PairCounts) -> f32 {
+        (counts.c_ab as f64 * counts.n_posts as f64 / (counts.c_a as f64 * counts.c_b as f64)) as f32
+    }
+}
+
+impl AssociationMeasure for ConditionalProbability {
+    fn score(&self, counts: PairCounts) -> f32 {
+        counts.c_ab as f32 / counts.c_a as f32
+    }
+
+    fn is_symmetric(&self) -> bool {
+        false
+    }
+}
+
+impl AssociationMeasure for LogLikelihoodRatio {
+    fn score(&self, counts: PairCounts) -> f32 {
+        if !counts.attracted() {
+            return 0.0;
+        }
+        let table = counts.table();
+        let n = counts.n_posts as f64;
+        let rows = [table[0][0] + table[0][1], table[1][0] + table[1][1]];
+        let cols = [table[0][0] + table[1][0], table[0][1] + table[1][1]];
+
+        let mut g2 = 0.0;
+        for i in 0..2 {
+            for j in 0..2 {
+                let observed = table[i][j];
+                if observed > 0.0 {
+                    g2 += observed * (observed * n / (rows[i] * cols[j])).ln();
+                }
+            }
+        }
+        (2.0 * g2) as f32
+    }
+}
+
+impl AssociationMeasure for ChiSquare {
+    fn score(&self, counts: PairCounts) -> f32 {
+        if !counts.attracted() {
+            return 0.0;
+        }
+        let [[o11, o12], [o21, o22]] = counts.table();
+        let n = counts.n_posts as f64;
+        let denominator = (o11 + o12) * (o21 + o22) * (o11 + o21) * (o12 + o22);
+        if denominator == 0.0 {
+            return 0.0;
+        }
+        (n * (o11 * o22 - o12 * o21).powi(2) / denominator) as f32
+    }
+}
+
+/// One of the measures of this module, as saved with the matrix of a model.
+/// New measures are only ever appended, so older headers keep decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Measure {
+    Npmi,
+    Pmi,
+    Ppmi,
+    Jaccard,
+    Dice,
+    Lift,
+    ConditionalProbability,
+    LogLikelihoodRatio,
+    ChiSquare,
+}
+
+/// Dispatches to the measure a model was built with.
+impl AssociationMeasure for Measure {
+    fn score(&self, counts: PairCounts) -> f32 {
+        match self {
+            Measure::Npmi => Npmi.score(counts),
+            Measure::Pmi => Pmi.score(counts),
+            Measure::Ppmi => Ppmi.score(counts),
+            Measure::Jaccard => Jaccard.score(counts),
+            Measure::Dice => Dice.score(counts),
+            Measure::Lift => Lift.score(counts),
+            Measure::ConditionalProbability => ConditionalProbability.score(counts),
+            Measure::LogLikelihoodRatio => LogLikelihoodRatio.score(counts),
+            Measure::ChiSquare => ChiSquare.score(counts),
+        }
+    }
+
+    fn is_symmetric(&self) -> bool {
+        *self != Measure::ConditionalProbability
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Index, Result, Value, CSR};
+
+/// Buffers writes to a [`CSR`] and merges them in a single pass on
+/// [`CSRBatch::commit`]. Dropping the batch without committing discards the
+/// writes.
+///
+/// When the same cell is written several times the last write wins.
+#[must_use = "writes are discarded unless the batch is committed"]
+pub struct CSRBatch<'a, V: Value, I: Index> {
+    csr: &'a mut CSR<V, I>,
+    pending: Vec<(usize, usize, Option<V>)>,
+}
+
+impl<V: Value, I: Index> CSRBatch<'_, V, I> {
+    pub fn insert(&mut self, row: usize, col: usize, val: V) {
+        let val = if val == V::ZERO { None } else { Some(val) };
+        self.pending.push((row, col, val));
+    }
+
+    pub fn remove(&mut self, row: usize, col: usize) {
+        self.pending.push((row, col, None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Merges the writes into the matrix. If any write is out of bounds the
+    /// matrix is left unchanged.
+    pub fn commit(mut self) -> Result<()> {
+        for &(row, col, _) in &self.pending {
+            self.csr.check_bounds(row, col)?;
+        }
+        self.merge();
+
+        Ok(())
+    }
+
+    fn merge(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        // stable sort, so the last write to a cell ends up last in its run
+        self.pending.sort_by_key(|&(r, c, _)| (r, c));
+
+        let csr = &mut *self.csr;
+        let mut row_ptr = Vec::with_capacity(csr.n_rows + 1);
+        let mut col_idx = Vec::with_capacity(csr.n_nz + self.pending.len());
+        let mut val = Vec::with_capacity(csr.n_nz + self.pending.len());
+
+        row_ptr.push(I::default());
+        let mut p = 0;
+        for row in 0..csr.n_rows {
+            let mut i = csr.row_ptr[row].index();
+            let row_end = csr.row_ptr[row + 1].index();
+
+            while i < row_end || (p < self.pending.len() && self.pending[p].0 == row) {
+                let pending_col = match self.pending.get(p) {
+                    Some(&(r, c, _)) if r == row => Some(c),
+                    _ => None,
+                };
+
+                match pending_col {
+                    Some(c) if i == row_end || c <= csr.col_idx[i].index() => {
+                        while p + 1 < self.pending.len()
+                            && self.pending[p + 1].0 == row
+                            && self.pending[p + 1].1 == c
+                        {
+                            p += 1;
+                        }
+                        if let Some(v) = self.pending[p].2 {
+                            col_idx.push(I::from_usize(c));
+                            val.push(v);
+                        }
+                        if i < row_end && csr.col_idx[i].index() == c {
+                            i += 1;
+                        }
+                        p += 1;
+                    }
+                    _ => {
+                        col_idx.push(csr.col_idx[i]);
+                        val.push(csr.val[i]);
+                        i += 1;
+                    }
+                }
+            }
+            row_ptr.push(I::from_usize(col_idx.len()));
+        }
+
+        csr.n_nz = col_idx.len();
+        csr.row_ptr = row_ptr;
+        csr.col_idx = col_idx;
+        csr.val = val;
+        self.pending.clear();
+    }
+}
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// Starts a batch of writes that are merged into the matrix together,
+    /// costing O(nnz + k log k) for k writes instead of O(nnz) per write.
+    pub fn batch(&mut self) -> CSRBatch<'_, V, I> {
+        CSRBatch {
+            csr: self,
+            pending: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Index, Value, CSR};
+
+/// Incremental COO-to-CSR builder.
+///
+/// Triples are buffered as they arrive and turned into a [`CSR`] in two
+/// passes: a counting sort that scatters them into their rows, then a per-row
+/// sort that orders columns and sums duplicate cells. Cells that sum to zero
+/// are not stored.
+///
+/// With [`CSRBuilder::max_buffered`] set, the buffer is compressed into a
+/// partial matrix whenever it fills up. Partials are merged pairwise like
+/// the digits of a binary counter, so each triple is merged O(log n) times
+/// and at most O(log n) partials are held, and the rest are summed in one
+/// k-way merge by [`CSRBuilder::build`].
+pub struct CSRBuilder<V = f32, I = usize> {
+    n_rows: usize,
+    n_cols: usize,
+    rows: Vec<I>,
+    cols: Vec<I>,
+    vals: Vec<V>,
+    max_buffered: Option<usize>,
+    /// Partial matrices with their level, which is strictly decreasing; a
+    /// partial of level `l` holds `2^l` flushes.
+    partials: Vec<(u32, CSR<V, I>)>,
+}
+
+impl<V: Value, I: Index> CSRBuilder<V, I> {
+    pub fn new(n_rows: usize, n_cols: usize) -> CSRBuilder<V, I> {
+        CSRBuilder::with_capacity(n_rows, n_cols, 0)
+    }
+
+    pub fn with_capacity(n_rows: usize, n_cols: usize, capacity: usize) -> CSRBuilder<V, I> {
+        CSRBuilder {
+            n_rows,
+            n_cols,
+            rows: Vec::with_capacity(capacity),
+            cols: Vec::with_capacity(capacity),
+            vals: Vec::with_capacity(capacity),
+            max_buffered: None,
+            partials: Vec::new(),
+        }
+    }
+
+    /// Compresses the buffer into a partial matrix once it holds `limit`
+    /// triples.
+    pub fn max_buffered(mut self, limit: usize) -> CSRBuilder<V, I> {
+        self.max_buffered = Some(limit.max(1));
+        self
+    }
+
+    pub fn push(&mut self, row: usize, col: usize, val: V) {
+        assert!(
+            row < self.n_rows && col < self.n_cols,
+            "index ({row}, {col}) out of bounds for {}x{} matrix",
+            self.n_rows,
+            self.n_cols
+        );
+
+        self.rows.push(I::from_usize(row));
+        self.cols.push(I::from_usize(col));
+        self.vals.push(val);
+
+        if self.max_buffered.is_some_and(|limit| self.rows.len() >= limit) {
+            self.flush();
+        }
+    }
+
+    /// Number of triples currently buffered.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn build(mut self) -> CSR<V, I> {
+        self.flush();
+        match self.partials.len() {
+            0 => CSR::new(self.n_rows, self.n_cols),
+            1 => self.partials.pop().expect("one partial").1,
+            _ => {
+                let partials: Vec<&CSR<V, I>> = self.partials.iter().map(|(_, m)| m).collect();
+                CSR::merge_add(&partials).expect("partial matrices have the same shape")
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        let rows = std::mem::take(&mut self.rows);
+        let cols = std::mem::take(&mut self.cols);
+        let vals = std::mem::take(&mut self.vals);
+        let mut partial = compress(self.n_rows, self.n_cols, rows, cols, vals);
+
+        let mut level = 0;
+        while self.partials.last().is_some_and(|&(l, _)| l == level) {
+            let (_, prev) = self.partials.pop().expect("checked above");
+            partial = CSR::merge_add(&[&prev, &partial]).expect("partial matrices have the same shape");
+            level += 1;
+        }
+        self.partials.push((level, partial));
+    }
+}
+
+impl<V: Value, I: Index> Extend<(usize, usize, V)> for CSRBuilder<V, I> {
+    fn extend<T: IntoIterator<Item = (usize, usize, V)>>(&mut self, iter: T) {
+        for (row, col, val) in iter {
+            self.push(row, col, val);
+        }
+    }
+}
+
+fn compress<V: Value, I: Index>(
+    n_rows: usize,
+    n_cols: usize,
+    rows: Vec<I>,
+    cols: Vec<I>,
+    vals: Vec<V>,
+) -> CSR<V, I> {
+    // pass 1: counting sort of the triples into their rows
+    let mut offsets = vec![0usize; n_rows + 1];
+    for &r in &rows {
+        offsets[r.index() + 1] += 1;
+    }
+    for r in 0..n_rows {
+        offsets[r + 1] += offsets[r];
+    }
+
+    let mut next = offsets[..n_rows].to_vec();
+    let mut col_idx = vec![I::default(); rows.len()];
+    let mut val = vec![V::ZERO; rows.len()];
+    for ((r, c), v) in rows.into_iter().zip(cols).zip(vals) {
+        let dest = &mut next[r.index()];
+        col_idx[*dest] = c;
+        val[*dest] = v;
+        *dest += 1;
+    }
+    drop(next);
+
+    // pass 2: sort each row by column and sum duplicates, compacting in place
+    let mut row_ptr = Vec::with_capacity(n_rows + 1);
+    let mut scratch: Vec<(I, V)> = Vec::new();
+    let mut write = 0;
+
+    row_ptr.push(I::default());
+    for r in 0..n_rows {
+        scratch.clear();
+        scratch.extend((offsets[r]..offsets[r + 1]).map(|i| (col_idx[i], val[i])));
+        scratch.sort_unstable_by_key(|&(c, _)| c);
+
+        let mut i = 0;
+        while i < scratch.len() {
+            let (c, mut sum) = scratch[i];
+            i += 1;
+            while i < scratch.len() && scratch[i].0 == c {
+                sum += scratch[i].1;
+                i += 1;
+            }
+            if sum != V::ZERO {
+                col_idx[write] = c;
+                val[write] = sum;
+                write += 1;
+            }
+        }
+        row_ptr.push(I::from_usize(write));
+    }
+    col_idx.truncate(write);
+    val.truncate(write);
+
+    CSR {
+        n_rows,
+        n_cols,
+        n_nz: write,
+        row_ptr,
+        col_idx,
+        val,
+    }
+}
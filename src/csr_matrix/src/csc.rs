@@ -0,0 +1,188 @@
+use crate::{Index, Value, CSR};
+use serde::{Deserialize, Serialize};
+
+/// Compressed sparse column matrix, the column-major twin of [`CSR`].
+///
+/// Column `j` is stored contiguously in `row_idx[col_ptr[j]..col_ptr[j + 1]]`
+/// with row indices sorted, so "which rows have an entry in column j" is a
+/// single slice instead of a scan over every row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CSC<V = f32, I = usize> {
+    pub n_rows: usize,
+    pub n_cols: usize,
+    pub n_nz: usize,
+    col_ptr: Vec<I>,
+    row_idx: Vec<I>,
+    val: Vec<V>,
+}
+
+/// Converts compressed storage along one axis into compressed storage along
+/// the other with a counting sort. Minor indices of the output stay sorted
+/// because the input is walked in major order.
+pub(crate) fn transpose_parts<V: Value, I: Index>(
+    n_major: usize,
+    n_minor: usize,
+    ptr: &[I],
+    idx: &[I],
+    val: &[V],
+) -> (Vec<I>, Vec<I>, Vec<V>) {
+    let mut counts = vec![0usize; n_minor + 1];
+    for &i in idx {
+        counts[i.index() + 1] += 1;
+    }
+    for i in 0..n_minor {
+        counts[i + 1] += counts[i];
+    }
+
+    let mut next = counts[..n_minor].to_vec();
+    let mut t_idx = vec![I::default(); idx.len()];
+    let mut t_val = vec![V::ZERO; val.len()];
+    for major in 0..n_major {
+        for k in ptr[major].index()..ptr[major + 1].index() {
+            let minor = idx[k].index();
+            let dest = next[minor];
+            t_idx[dest] = I::from_usize(major);
+            t_val[dest] = val[k];
+            next[minor] += 1;
+        }
+    }
+
+    let t_ptr = counts.into_iter().map(I::from_usize).collect();
+    (t_ptr, t_idx, t_val)
+}
+
+impl<V: Value, I: Index> CSC<V, I> {
+    pub fn new(n_rows: usize, n_cols: usize) -> CSC<V, I> {
+        CSC {
+            n_rows,
+            n_cols,
+            n_nz: 0,
+            col_ptr: vec![I::default(); n_cols + 1],
+            row_idx: Vec::new(),
+            val: Vec::new(),
+        }
+    }
+
+    pub fn from_csr(csr: &CSR<V, I>) -> CSC<V, I> {
+        let (col_ptr, row_idx, val) =
+            transpose_parts(csr.n_rows, csr.n_cols, &csr.row_ptr, &csr.col_idx, &csr.val);
+
+        CSC {
+            n_rows: csr.n_rows,
+            n_cols: csr.n_cols,
+            n_nz: csr.n_nz,
+            col_ptr,
+            row_idx,
+            val,
+        }
+    }
+
+    pub fn to_csr(&self) -> CSR<V, I> {
+        let (row_ptr, col_idx, val) =
+            transpose_parts(self.n_cols, self.n_rows, &self.col_ptr, &self.row_idx, &self.val);
+
+        CSR {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            n_nz: self.n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+
+    pub fn value(&self, row: usize, col: usize) -> Option<V> {
+        if row >= self.n_rows || col >= self.n_cols {
+            return None;
+        }
+        let col_start = self.col_ptr[col].index();
+        let col_end = self.col_ptr[col + 1].index();
+        match self.row_idx[col_start..col_end].binary_search(&I::from_usize(row)) {
+            Ok(i) => Some(self.val[col_start + i]),
+            Err(_) => Some(V::ZERO),
+        }
+    }
+
+    pub fn get_col(&self, col: usize) -> Option<Vec<V>> {
+        if col >= self.n_cols {
+            return None;
+        }
+
+        let mut dense = vec![V::ZERO; self.n_rows];
+        for (row, val) in self.inter_col(col) {
+            dense[row] = val;
+        }
+
+        Some(dense)
+    }
+
+    /// Iterates over the stored `(row, value)` pairs of `col`.
+    pub fn inter_col(&self, col: usize) -> CSCColIter<'_, V, I> {
+        CSCColIter {
+            csc: self,
+            col_end: self.col_ptr[col + 1].index(),
+            idx: self.col_ptr[col].index(),
+        }
+    }
+
+    /// Iterates over every stored `(row, col, value)` in column-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, V)> + '_ {
+        (0..self.n_cols).flat_map(move |col| {
+            self.inter_col(col).map(move |(row, val)| (row, col, val))
+        })
+    }
+}
+
+impl<V: Value, I: Index> From<&CSR<V, I>> for CSC<V, I> {
+    fn from(csr: &CSR<V, I>) -> CSC<V, I> {
+        CSC::from_csr(csr)
+    }
+}
+
+impl<V: Value, I: Index> From<&CSC<V, I>> for CSR<V, I> {
+    fn from(csc: &CSC<V, I>) -> CSR<V, I> {
+        csc.to_csr()
+    }
+}
+
+pub struct CSCColIter<'a, V, I> {
+    csc: &'a CSC<V, I>,
+    col_end: usize,
+    idx: usize,
+}
+
+impl<V: Value, I: Index> Iterator for CSCColIter<'_, V, I> {
+    type Item = (usize, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.col_end {
+            return None;
+        }
+
+        let row = self.csc.row_idx[self.idx].index();
+        let val = self.csc.val[self.idx];
+        self.idx += 1;
+
+        Some((row, val))
+    }
+}
+
+impl<V: Value, I: Index> CSR<V, I> {
+    pub fn transpose(&self) -> CSR<V, I> {
+        let (row_ptr, col_idx, val) =
+            transpose_parts(self.n_rows, self.n_cols, &self.row_ptr, &self.col_idx, &self.val);
+
+        CSR {
+            n_rows: self.n_cols,
+            n_cols: self.n_rows,
+            n_nz: self.n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+
+    pub fn to_csc(&self) -> CSC<V, I> {
+        CSC::from_csr(self)
+    }
+}
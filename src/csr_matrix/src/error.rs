@@ -0,0 +1,88 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Operands have incompatible shapes. Vectors are `(len, 1)`.
+    DimensionMismatch {
+        lhs: (usize, usize),
+        rhs: (usize, usize),
+    },
+    /// An operation over many matrices was given none.
+    NoMatrices,
+    NotSquare {
+        n_rows: usize,
+        n_cols: usize,
+    },
+    NotSymmetric,
+    /// A write to a cell outside the matrix.
+    OutOfBounds {
+        row: usize,
+        col: usize,
+        n_rows: usize,
+        n_cols: usize,
+    },
+    /// An element-wise difference does not fit in the value type, such as
+    /// `0 - 1` for unsigned values.
+    Underflow {
+        row: usize,
+        col: usize,
+    },
+    /// A matrix meant to hold an upper triangle has entries below the
+    /// diagonal.
+    NotUpperTriangular,
+    Io(io::Error),
+    /// A matrix file could not be parsed.
+    Format(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DimensionMismatch { lhs, rhs } => write!(
+                f,
+                "Dimensions do not match: {}x{} and {}x{}",
+                lhs.0, lhs.1, rhs.0, rhs.1
+            ),
+            Error::NoMatrices => write!(f, "No matrices given"),
+            Error::NotSquare { n_rows, n_cols } => write!(f, "Matrix is not square: {n_rows}x{n_cols}"),
+            Error::NotSymmetric => write!(f, "Matrix is not symmetric"),
+            Error::OutOfBounds { row, col, n_rows, n_cols } => {
+                write!(f, "Index ({row}, {col}) out of bounds for {n_rows}x{n_cols} matrix")
+            }
+            Error::Underflow { row, col } => write!(f, "Difference underflows at ({row}, {col})"),
+            Error::NotUpperTriangular => write!(f, "Matrix has entries below the diagonal"),
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Format(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<String> for Error {
+    fn from(reason: String) -> Error {
+        Error::Format(reason)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(reason: &str) -> Error {
+        Error::Format(reason.to_string())
+    }
+}
@@ -1,25 +1,25 @@
-use crate::CSR;
+use crate::{Index, Value, CSR};
 
-pub struct CSRIter<'a> {
-    csr: &'a CSR,
+pub struct CSRIter<'a, V, I> {
+    csr: &'a CSR<V, I>,
     row: usize,
     idx: usize,
 }
 
-impl<'a> Iterator for CSRIter<'a> {
-    type Item = (usize, usize, f32);
+impl<'a, V: Value, I: Index> Iterator for CSRIter<'a, V, I> {
+    type Item = (usize, usize, V);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.idx >= self.csr.n_nz {
             return None;
         }
 
-        while self.idx >= self.csr.row_ptr[self.row + 1] {
+        while self.idx >= self.csr.row_ptr[self.row + 1].index() {
             self.row += 1;
         }
 
         let row = self.row;
-        let col = self.csr.col_idx[self.idx];
+        let col = self.csr.col_idx[self.idx].index();
         let val = self.csr.val[self.idx];
         self.idx += 1;
 
@@ -27,38 +27,46 @@ impl<'a> Iterator for CSRIter<'a> {
     }
 }
 
-impl<'a> IntoIterator for &'a CSR {
-    type Item = (usize, usize, f32);
-    type IntoIter = CSRIter<'a>;
+impl<'a, V: Value, I: Index> IntoIterator for &'a CSR<V, I> {
+    type Item = (usize, usize, V);
+    type IntoIter = CSRIter<'a, V, I>;
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-pub struct CSRRowIter<'a> {
-    csr: &'a CSR,
-    row_end: usize,
+pub struct CSRRowIter<'a, V, I> {
+    col_idx: &'a [I],
+    val: &'a [V],
     idx: usize,
 }
 
-impl<'a> Iterator for CSRRowIter<'a> {
-    type Item = (usize, f32);
+impl<'a, V, I> CSRRowIter<'a, V, I> {
+    /// Iterates the entries of one row, given as its slices of `col_idx`
+    /// and `val`.
+    pub(crate) fn new(col_idx: &'a [I], val: &'a [V]) -> CSRRowIter<'a, V, I> {
+        CSRRowIter { col_idx, val, idx: 0 }
+    }
+}
+
+impl<'a, V: Value, I: Index> Iterator for CSRRowIter<'a, V, I> {
+    type Item = (usize, V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.idx >= self.row_end {
+        if self.idx >= self.col_idx.len() {
             return None;
         }
 
-        let col = self.csr.col_idx[self.idx];
-        let val = self.csr.val[self.idx];
+        let col = self.col_idx[self.idx].index();
+        let val = self.val[self.idx];
         self.idx += 1;
 
         Some((col, val))
     }
 }
 
-impl CSR {
-    pub fn iter(&self) -> CSRIter {
+impl<V: Value, I: Index> CSR<V, I> {
+    pub fn iter(&self) -> CSRIter<'_, V, I> {
         CSRIter {
             csr: self,
             row: 0,
@@ -66,14 +74,10 @@ impl CSR {
         }
     }
 
-    pub fn inter_row(&self, row: usize) -> CSRRowIter {
-        let row_start = self.row_ptr[row];
-        let row_end = self.row_ptr[row + 1];
+    pub fn inter_row(&self, row: usize) -> CSRRowIter<'_, V, I> {
+        let row_start = self.row_ptr[row].index();
+        let row_end = self.row_ptr[row + 1].index();
 
-        CSRRowIter{
-            csr: self,
-            row_end,
-            idx: row_start,
-        }
+        CSRRowIter::new(&self.col_idx[row_start..row_end], &self.val[row_start..row_end])
     }
 }
@@ -1,157 +1,233 @@
+pub mod batch;
+pub mod builder;
+pub mod csc;
+pub mod error;
 pub mod iter;
+pub mod mtx;
+pub mod npz;
+pub mod num;
 pub mod ops;
+mod prune;
+pub mod slice;
+mod stack;
+pub mod symmetric;
+pub mod view;
+
+pub use builder::CSRBuilder;
+pub use csc::CSC;
+pub use error::{Error, Result};
+pub use num::{Index, Value};
+pub use slice::IndexMap;
+pub use symmetric::{ColumnIndex, ColumnIndexView, SymmetricCSR, SymmetricCSRView};
+pub use view::CSRView;
 
 use rustc_hash::FxHashMap;
-use serde::{Deserialize, Serialize};
-use std::error::Error;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CSR {
+#[derive(Debug, Clone, Serialize)]
+pub struct CSR<V = f32, I = usize> {
     pub n_rows: usize,
     pub n_cols: usize,
     pub n_nz: usize,
-    row_ptr: Vec<usize>,
-    col_idx: Vec<usize>,
-    val: Vec<f32>,
+    row_ptr: Vec<I>,
+    col_idx: Vec<I>,
+    val: Vec<V>,
 }
 
-impl CSR {
-    pub fn new(n_rows: usize, n_cols: usize) -> CSR {
+impl<V: Value, I: Index> CSR<V, I> {
+    pub fn new(n_rows: usize, n_cols: usize) -> CSR<V, I> {
         CSR {
             n_rows,
             n_cols,
             n_nz: 0,
-            row_ptr: vec![0; n_rows + 1],
+            row_ptr: vec![I::default(); n_rows + 1],
             col_idx: Vec::new(),
             val: Vec::new(),
         }
     }
 
-    pub fn from_triples(triplets: &Vec<(usize, usize, f32)>, n_rows: usize, n_cols: usize) -> CSR {
-        let mut rows: Vec<Vec<(usize, f32)>> = vec![vec![]; n_rows];
-        for (r, c, v) in triplets {
-            rows[*r].push((*c, *v));
-        }
-        for row in rows.iter_mut() {
-            row.sort_by_key(|(c, _)| *c);
-        }
-
-        let mut row_ptr: Vec<usize> = Vec::with_capacity(n_rows + 1);
-        let mut col_idx: Vec<usize> = Vec::new();
-        let mut val: Vec<f32> = Vec::new();
-        let mut n_nz = 0;
-
-        row_ptr.push(0);
-        for row in &rows {
-            if row.is_empty() {
-                row_ptr.push(*row_ptr.last().unwrap());
-                continue;
-            }
-            for &(c, v) in row {
-                col_idx.push(c);
-                val.push(v);
-                n_nz += 1;
-            }
-            row_ptr.push(col_idx.len());
-        }
-
-        CSR {
+    /// Builds a matrix from its arrays, failing unless they agree with each
+    /// other and the shape: `row_ptr` runs from zero to the number of
+    /// entries without decreasing, and every row holds strictly increasing
+    /// columns below `n_cols`.
+    pub fn try_from_parts(
+        n_rows: usize,
+        n_cols: usize,
+        row_ptr: Vec<I>,
+        col_idx: Vec<I>,
+        val: Vec<V>,
+    ) -> Result<CSR<V, I>> {
+        let csr = CSR {
             n_rows,
             n_cols,
-            n_nz,
+            n_nz: col_idx.len(),
             row_ptr,
             col_idx,
             val,
-        }
+        };
+        csr.view().check()?;
+
+        Ok(csr)
+    }
+
+    /// Builds a matrix from `(row, col, value)` triples; duplicate cells are
+    /// summed.
+    pub fn from_triples(triplets: &[(usize, usize, V)], n_rows: usize, n_cols: usize) -> CSR<V, I> {
+        let mut builder = CSRBuilder::with_capacity(n_rows, n_cols, triplets.len());
+        builder.extend(triplets.iter().copied());
+        builder.build()
     }
 
     pub fn from_fxhash(
-        fxhash: &FxHashMap<(usize, usize), f32>,
+        fxhash: &FxHashMap<(usize, usize), V>,
         n_rows: usize,
         n_cols: usize,
-    ) -> CSR {
-        let mut rows: Vec<Vec<(usize, f32)>> = vec![vec![]; n_rows];
-        for (&(r, c), &v) in fxhash {
-            rows[r].push((c, v));
+    ) -> CSR<V, I> {
+        let mut builder = CSRBuilder::with_capacity(n_rows, n_cols, fxhash.len());
+        builder.extend(fxhash.iter().map(|(&(r, c), &v)| (r, c, v)));
+        builder.build()
+    }
+
+    /// Returns the value at `(row, col)`, zero if it is not stored, or `None`
+    /// if the index is out of bounds.
+    pub fn value(&self, row: usize, col: usize) -> Option<V> {
+        if row >= self.n_rows || col >= self.n_cols {
+            return None;
         }
-        for row in rows.iter_mut() {
-            row.sort_by_key(|(c, _)| *c);
+        match self.position(row, col) {
+            Ok(i) => Some(self.val[i]),
+            Err(_) => Some(V::ZERO),
         }
+    }
 
-        let mut row_ptr: Vec<usize> = Vec::with_capacity(n_rows + 1);
-        let mut col_idx: Vec<usize> = Vec::new();
-        let mut val: Vec<f32> = Vec::new();
-        let mut n_nz = 0;
+    /// Looks up many cells at once, see [`CSR::value`]. Consecutive lookups
+    /// in the same row reuse the row bounds.
+    pub fn values(&self, idxs: &[(usize, usize)]) -> Vec<Option<V>> {
+        let mut out = Vec::with_capacity(idxs.len());
+        let mut cur_row = usize::MAX;
+        let mut cols: &[I] = &[];
+        let mut row_start = 0;
 
-        row_ptr.push(0);
-        for row in &rows {
-            if row.is_empty() {
-                row_ptr.push(*row_ptr.last().unwrap());
+        for &(row, col) in idxs {
+            if row >= self.n_rows || col >= self.n_cols {
+                out.push(None);
                 continue;
             }
-            for &(c, v) in row {
-                col_idx.push(c);
-                val.push(v);
-                n_nz += 1;
+            if row != cur_row {
+                cur_row = row;
+                row_start = self.row_ptr[row].index();
+                cols = &self.col_idx[row_start..self.row_ptr[row + 1].index()];
             }
-            row_ptr.push(col_idx.len());
+            out.push(match cols.binary_search(&I::from_usize(col)) {
+                Ok(i) => Some(self.val[row_start + i]),
+                Err(_) => Some(V::ZERO),
+            });
         }
 
-        CSR {
-            n_rows,
-            n_cols,
-            n_nz,
-            row_ptr,
-            col_idx,
-            val,
-        }
+        out
     }
 
-    pub fn value(&self, row: usize, col: usize) -> Option<f32> {
-        if row > self.n_rows || col > self.n_cols {
+    pub fn get_row(&self, row: usize) -> Option<Vec<V>> {
+        if row >= self.n_rows {
             return None;
         }
-        let row_start = self.row_ptr[row];
-        let row_end = self.row_ptr[row + 1];
-        for i in row_start..row_end {
-            if self.col_idx[i] == col {
-                return Some(self.val[i].clone());
+
+        let mut dense = vec![V::ZERO; self.n_cols];
+        for (col, val) in self.inter_row(row) {
+            dense[col] = val;
+        }
+
+        Some(dense)
+    }
+
+    /// Binary searches the sorted columns of `row`. `Ok` holds the position
+    /// of the entry in `col_idx`/`val`, `Err` the position it would be
+    /// inserted at.
+    pub(crate) fn position(&self, row: usize, col: usize) -> std::result::Result<usize, usize> {
+        let row_start = self.row_ptr[row].index();
+        let row_end = self.row_ptr[row + 1].index();
+        self.col_idx[row_start..row_end]
+            .binary_search(&I::from_usize(col))
+            .map(|i| row_start + i)
+            .map_err(|i| row_start + i)
+    }
+
+    /// Fails with [`Error::OutOfBounds`] if `(row, col)` is not a cell.
+    pub(crate) fn check_bounds(&self, row: usize, col: usize) -> Result<()> {
+        if row < self.n_rows && col < self.n_cols {
+            Ok(())
+        } else {
+            Err(Error::OutOfBounds {
+                row,
+                col,
+                n_rows: self.n_rows,
+                n_cols: self.n_cols,
+            })
+        }
+    }
+
+    /// Sets `(row, col)` to `val` and returns the previous value, if any.
+    /// Inserting zero removes the entry, so no explicit zeros are stored.
+    ///
+    /// Each call shifts the tail of the matrix, so for many writes use
+    /// [`CSR::batch`] instead.
+    pub fn insert(&mut self, row: usize, col: usize, val: V) -> Result<Option<V>> {
+        self.check_bounds(row, col)?;
+        if val == V::ZERO {
+            return self.remove(row, col);
+        }
+
+        match self.position(row, col) {
+            Ok(i) => Ok(Some(std::mem::replace(&mut self.val[i], val))),
+            Err(i) => {
+                self.col_idx.insert(i, I::from_usize(col));
+                self.val.insert(i, val);
+                for ptr in &mut self.row_ptr[row + 1..] {
+                    *ptr = I::from_usize(ptr.index() + 1);
+                }
+                self.n_nz += 1;
+                Ok(None)
             }
         }
-        Some(0.0)
     }
 
-    pub fn get_row(&self, row: usize) -> Option<Vec<f32>> {
-        if row > self.n_rows {
-            return None;
+    /// Removes `(row, col)` and returns its value, if it was stored.
+    pub fn remove(&mut self, row: usize, col: usize) -> Result<Option<V>> {
+        self.check_bounds(row, col)?;
+        let Ok(i) = self.position(row, col) else {
+            return Ok(None);
+        };
+
+        self.col_idx.remove(i);
+        let old = self.val.remove(i);
+        for ptr in &mut self.row_ptr[row + 1..] {
+            *ptr = I::from_usize(ptr.index() - 1);
         }
-        
-        let row_start = self.row_ptr[row];
-        let row_end = self.row_ptr[row + 1];
-        
-        let mut row = vec![0.0; self.n_cols];
-        for i in row_start..row_end{
-            row[self.col_idx[i]] = self.val[i];
-        } 
-        
-        Some(row)
+        self.n_nz -= 1;
+        Ok(Some(old))
     }
 
-    pub fn insert(&mut self, row: usize, col: usize, val: f32) {
-        todo!()
+    /// Replaces `(row, col)` with `f(current)`, where a missing entry reads as
+    /// zero, and returns the new value.
+    pub fn update<F>(&mut self, row: usize, col: usize, f: F) -> Result<V>
+    where
+        F: FnOnce(V) -> V,
+    {
+        self.check_bounds(row, col)?;
+        let new = f(self.value(row, col).unwrap_or(V::ZERO));
+        self.insert(row, col, new)?;
+        Ok(new)
     }
 
+    /// Approximate heap and inline size of the matrix in bytes.
     pub fn size(&self) -> usize {
-        (self.n_rows + 1) * size_of::<usize>()
-            + self.n_nz * size_of::<usize>()
-            + self.n_nz * size_of::<u32>()
-            + size_of::<Vec<f32>>()
-            + size_of::<Vec<usize>>() * 2
-            + size_of::<f32>()
-            + size_of::<usize>() * 2
+        (self.n_rows + 1) * size_of::<I>()
+            + self.n_nz * size_of::<I>()
+            + self.n_nz * size_of::<V>()
+            + size_of::<Self>()
     }
 
-    pub fn add_in_place(&mut self, other: &CSR) -> Result<(), Box<dyn Error>> {
+    pub fn add_in_place(&mut self, other: &CSR<V, I>) -> Result<()> {
         let res = (&*self + other)?;
         self.n_nz = res.n_nz;
         self.row_ptr = res.row_ptr;
@@ -161,3 +237,32 @@ impl CSR {
         Ok(())
     }
 }
+
+/// How a [`CSR`] is serialized.
+#[derive(Deserialize)]
+#[serde(rename = "CSR")]
+struct Parts<V, I> {
+    n_rows: usize,
+    n_cols: usize,
+    n_nz: usize,
+    row_ptr: Vec<I>,
+    col_idx: Vec<I>,
+    val: Vec<V>,
+}
+
+/// Checks the arrays as [`CSR::try_from_parts`] does, so a corrupt encoding
+/// fails to decode rather than giving a matrix whose lookups panic.
+impl<'de, V, I> Deserialize<'de> for CSR<V, I>
+where
+    V: Value + Deserialize<'de>,
+    I: Index + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let parts = Parts::<V, I>::deserialize(deserializer)?;
+        if parts.n_nz != parts.col_idx.len() {
+            return Err(serde::de::Error::custom("CSR arrays do not match the matrix shape"));
+        }
+        CSR::try_from_parts(parts.n_rows, parts.n_cols, parts.row_ptr, parts.col_idx, parts.val)
+            .map_err(serde::de::Error::custom)
+    }
+}
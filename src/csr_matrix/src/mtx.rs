@@ -0,0 +1,212 @@
+//! Matrix Market coordinate format, see
+//! <https://math.nist.gov/MatrixMarket/formats.html>.
+
+use crate::{CSRBuilder, Error, Index, Result, SymmetricCSR, Value, CSR};
+use std::fmt::Display;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+/// Value type with a Matrix Market field, `integer` or `real`.
+pub trait MtxValue: Value + Display + FromStr {
+    const FIELD: &'static str;
+}
+
+macro_rules! impl_mtx_value {
+    ($field:literal: $($t:ty),*) => {$(
+        impl MtxValue for $t {
+            const FIELD: &'static str = $field;
+        }
+    )*};
+}
+
+impl_mtx_value!("integer": u32, u64, i32, i64);
+impl_mtx_value!("real": f32, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symmetry {
+    General,
+    Symmetric,
+}
+
+struct Header {
+    symmetry: Symmetry,
+    pattern: bool,
+    n_rows: usize,
+    n_cols: usize,
+    n_entries: usize,
+}
+
+impl<V: MtxValue, I: Index> CSR<V, I> {
+    /// Writes the matrix as a `general` coordinate file.
+    pub fn write_mtx<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "%%MatrixMarket matrix coordinate {} general", V::FIELD)?;
+        writeln!(writer, "{} {} {}", self.n_rows, self.n_cols, self.n_nz)?;
+        for (row, col, val) in self {
+            writeln!(writer, "{} {} {}", row + 1, col + 1, val)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a coordinate file. `symmetric` files are expanded to both
+    /// triangles, `pattern` files get a value of one for every entry.
+    pub fn read_mtx<R: BufRead>(reader: R) -> Result<CSR<V, I>> {
+        let (_, builder) = read_entries(reader, true)?;
+        Ok(builder.build())
+    }
+}
+
+impl<V: MtxValue, I: Index> SymmetricCSR<V, I> {
+    /// Writes the matrix as a `symmetric` coordinate file, which by the
+    /// format's convention holds the lower triangle.
+    pub fn write_mtx<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "%%MatrixMarket matrix coordinate {} symmetric", V::FIELD)?;
+        writeln!(writer, "{} {} {}", self.n_rows(), self.n_cols(), self.n_stored())?;
+        for (row, col, val) in self.iter_upper() {
+            writeln!(writer, "{} {} {}", col + 1, row + 1, val)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a `symmetric` coordinate file, or a `general` one whose entries
+    /// turn out to be symmetric.
+    pub fn read_mtx<R: BufRead>(reader: R) -> Result<SymmetricCSR<V, I>> {
+        let (header, builder) = read_entries(reader, false)?;
+        if header.n_rows != header.n_cols {
+            return Err(Error::NotSquare {
+                n_rows: header.n_rows,
+                n_cols: header.n_cols,
+            });
+        }
+
+        match header.symmetry {
+            Symmetry::Symmetric => SymmetricCSR::from_upper(builder.build()),
+            Symmetry::General => SymmetricCSR::from_csr(&builder.build()),
+        }
+    }
+}
+
+/// Parses the header and entries into a builder. Entries of symmetric files
+/// are either mirrored into both triangles (`expand_symmetric`) or moved to
+/// the upper triangle.
+fn read_entries<V, I, R>(
+    reader: R,
+    expand_symmetric: bool,
+) -> Result<(Header, CSRBuilder<V, I>)>
+where
+    V: MtxValue,
+    I: Index,
+    R: BufRead,
+{
+    let mut lines = reader.lines();
+
+    let banner = lines.next().ok_or("Empty Matrix Market file")??;
+    let mut header = parse_banner::<V>(&banner)?;
+
+    let size_line = loop {
+        let line = lines.next().ok_or("Missing Matrix Market size line")??;
+        if !line.trim().is_empty() && !line.starts_with('%') {
+            break line;
+        }
+    };
+    let mut size = size_line.split_whitespace().map(str::parse::<usize>);
+    let mut next_size = || -> Result<usize> {
+        size.next()
+            .and_then(|n| n.ok())
+            .ok_or_else(|| "Malformed Matrix Market size line".into())
+    };
+    header.n_rows = next_size()?;
+    header.n_cols = next_size()?;
+    header.n_entries = next_size()?;
+
+    let mut builder = CSRBuilder::with_capacity(header.n_rows, header.n_cols, header.n_entries);
+    let mut n_read = 0;
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('%') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let mut index = |name: &str, n: usize| -> Result<usize> {
+            let i: usize = fields
+                .next()
+                .and_then(|i| i.parse().ok())
+                .ok_or("Malformed Matrix Market entry")?;
+            if i == 0 || i > n {
+                return Err(format!("{name} index {i} out of bounds in Matrix Market entry").into());
+            }
+            Ok(i - 1)
+        };
+        let row = index("Row", header.n_rows)?;
+        let col = index("Column", header.n_cols)?;
+        let val = if header.pattern {
+            V::ONE
+        } else {
+            fields
+                .next()
+                .ok_or("Malformed Matrix Market entry")?
+                .parse::<V>()
+                .map_err(|_| format!("Invalid {} value in Matrix Market entry", V::FIELD))?
+        };
+
+        match header.symmetry {
+            Symmetry::General => builder.push(row, col, val),
+            Symmetry::Symmetric if expand_symmetric => {
+                builder.push(row, col, val);
+                if row != col {
+                    builder.push(col, row, val);
+                }
+            }
+            Symmetry::Symmetric => builder.push(row.min(col), row.max(col), val),
+        }
+        n_read += 1;
+    }
+
+    if n_read != header.n_entries {
+        return Err(format!(
+            "Matrix Market file declares {} entries but has {n_read}",
+            header.n_entries
+        )
+        .into());
+    }
+
+    Ok((header, builder))
+}
+
+fn parse_banner<V: MtxValue>(banner: &str) -> Result<Header> {
+    let words: Vec<String> = banner.split_whitespace().map(str::to_lowercase).collect();
+    if words.len() != 5 || words[0] != "%%matrixmarket" || words[1] != "matrix" {
+        return Err("Not a Matrix Market matrix file".into());
+    }
+    if words[2] != "coordinate" {
+        return Err(format!("Unsupported Matrix Market format '{}'", words[2]).into());
+    }
+
+    let pattern = match words[3].as_str() {
+        "pattern" => true,
+        "integer" => false,
+        "real" if V::FIELD == "real" => false,
+        field => {
+            return Err(format!("Cannot read Matrix Market field '{field}' as {}", V::FIELD).into());
+        }
+    };
+    let symmetry = match words[4].as_str() {
+        "general" => Symmetry::General,
+        "symmetric" => Symmetry::Symmetric,
+        symmetry => {
+            return Err(format!("Unsupported Matrix Market symmetry '{symmetry}'").into());
+        }
+    };
+
+    Ok(Header {
+        symmetry,
+        pattern,
+        n_rows: 0,
+        n_cols: 0,
+        n_entries: 0,
+    })
+}
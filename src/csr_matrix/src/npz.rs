@@ -0,0 +1,246 @@
+//! The `scipy.sparse.save_npz` layout: an uncompressed zip of `.npy` files
+//! named `data`, `indices`, `indptr`, `shape` and `format`, see
+//! <https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html>.
+
+use crate::{CSRBuilder, Error, Index, Result, Value, CSR};
+use std::io::{BufWriter, Read, Seek, Write};
+use zip::write::SimpleFileOptions;
+use zip::result::ZipError;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Value type with a little-endian NumPy dtype.
+pub trait NpyValue: Value {
+    const DESCR: &'static str;
+
+    fn write_le<W: Write>(self, writer: &mut W) -> std::io::Result<()>;
+
+    /// `bytes` is exactly `size_of::<Self>()` long.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_npy_value {
+    ($($t:ty => $descr:literal),*) => {$(
+        impl NpyValue for $t {
+            const DESCR: &'static str = $descr;
+
+            fn write_le<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn read_le(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().expect("slice has the size of the type"))
+            }
+        }
+    )*};
+}
+
+impl_npy_value!(
+    u32 => "<u4", u64 => "<u8", i32 => "<i4", i64 => "<i8", f32 => "<f4", f64 => "<f8"
+);
+
+/// A parsed `.npy` file.
+struct Npy {
+    descr: String,
+    shape: Vec<usize>,
+    data: Vec<u8>,
+}
+
+impl<V: NpyValue, I: Index> CSR<V, I> {
+    /// Writes the matrix like `scipy.sparse.save_npz(..., compressed=False)`.
+    /// `indices` and `indptr` are int32 when they fit, like SciPy's own.
+    pub fn write_npz<W: Write + Seek>(&self, writer: W) -> Result<()> {
+        let mut zip = ZipWriter::new(writer);
+
+        let small = self.n_cols <= i32::MAX as usize && self.n_nz <= i32::MAX as usize;
+        let indices = self.col_idx.iter().map(|c| c.index());
+        let indptr = self.row_ptr.iter().map(|p| p.index());
+        if small {
+            write_array(&mut zip, "indices", &[self.n_nz], indices.map(|i| i as i32))?;
+            write_array(&mut zip, "indptr", &[self.n_rows + 1], indptr.map(|p| p as i32))?;
+        } else {
+            write_array(&mut zip, "indices", &[self.n_nz], indices.map(|i| i as i64))?;
+            write_array(&mut zip, "indptr", &[self.n_rows + 1], indptr.map(|p| p as i64))?;
+        }
+
+        let format = npy_header("|S3", &[]);
+        start_file(&mut zip, "format", format.len() + 3)?;
+        zip.write_all(&format)?;
+        zip.write_all(b"csr")?;
+
+        let shape = [self.n_rows as i64, self.n_cols as i64];
+        write_array(&mut zip, "shape", &[2], shape.into_iter())?;
+        write_array(&mut zip, "data", &[self.n_nz], self.val.iter().copied())?;
+
+        zip.finish().map_err(zip_error)?;
+
+        Ok(())
+    }
+
+    /// Reads a file written by `scipy.sparse.save_npz` (compressed or not)
+    /// holding a CSR matrix whose `data` has the dtype of `V`. Unsorted or
+    /// duplicate indices are summed and explicit zeros dropped.
+    pub fn read_npz<R: Read + Seek>(reader: R) -> Result<CSR<V, I>> {
+        let mut zip = ZipArchive::new(reader).map_err(zip_error)?;
+
+        let format = read_npy(&mut zip, "format")?;
+        if !format.descr.starts_with("|S") || format.data != b"csr" {
+            return Err("Not a CSR matrix npz file".into());
+        }
+
+        let shape = read_indices(read_npy(&mut zip, "shape")?)?;
+        let [n_rows, n_cols] = shape[..] else {
+            return Err("Matrix shape must have two dimensions".into());
+        };
+
+        let data = read_npy(&mut zip, "data")?;
+        if data.descr != V::DESCR {
+            return Err(format!("Cannot read npz data of dtype '{}' as '{}'", data.descr, V::DESCR).into());
+        }
+        let val: Vec<V> = data.data.chunks_exact(size_of::<V>()).map(V::read_le).collect();
+        let col_idx = read_indices(read_npy(&mut zip, "indices")?)?;
+        let row_ptr = read_indices(read_npy(&mut zip, "indptr")?)?;
+
+        if row_ptr.len() != n_rows + 1
+            || row_ptr[0] != 0
+            || row_ptr.windows(2).any(|w| w[0] > w[1])
+            || row_ptr[n_rows] != col_idx.len()
+            || col_idx.len() != val.len()
+        {
+            return Err("Inconsistent CSR arrays in npz file".into());
+        }
+        if col_idx.iter().any(|&c| c >= n_cols) {
+            return Err("Column index out of bounds in npz file".into());
+        }
+
+        let mut builder = CSRBuilder::with_capacity(n_rows, n_cols, val.len());
+        for row in 0..n_rows {
+            for i in row_ptr[row]..row_ptr[row + 1] {
+                builder.push(row, col_idx[i], val[i]);
+            }
+        }
+
+        Ok(builder.build())
+    }
+}
+
+fn zip_error(e: ZipError) -> Error {
+    match e {
+        ZipError::Io(e) => Error::Io(e),
+        e => Error::Format(format!("Invalid npz file: {e}")),
+    }
+}
+
+fn start_file<W: Write + Seek>(zip: &mut ZipWriter<W>, name: &str, len: usize) -> Result<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(len >= u32::MAX as usize);
+    zip.start_file(format!("{name}.npy"), options).map_err(zip_error)?;
+    Ok(())
+}
+
+fn write_array<W, T>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    shape: &[usize],
+    values: impl Iterator<Item = T>,
+) -> Result<()>
+where
+    W: Write + Seek,
+    T: NpyValue,
+{
+    let header = npy_header(T::DESCR, shape);
+    start_file(zip, name, header.len() + shape.iter().product::<usize>() * size_of::<T>())?;
+
+    let mut writer = BufWriter::new(zip);
+    writer.write_all(&header)?;
+    for v in values {
+        v.write_le(&mut writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Version 1.0 header, padded so the data starts 64-byte aligned.
+fn npy_header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape = match shape {
+        [n] => format!("({n},)"),
+        _ => format!("({})", shape.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")),
+    };
+    let mut dict = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}");
+    let unpadded = NPY_MAGIC.len() + 4 + dict.len() + 1;
+    dict.extend(std::iter::repeat_n(' ', unpadded.next_multiple_of(64) - unpadded));
+    dict.push('\n');
+
+    let mut header = Vec::with_capacity(NPY_MAGIC.len() + 4 + dict.len());
+    header.extend_from_slice(NPY_MAGIC);
+    header.extend_from_slice(&[1, 0]);
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+fn read_npy<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<Npy> {
+    let mut bytes = Vec::new();
+    zip.by_name(&format!("{name}.npy"))
+        .map_err(|_| format!("Missing '{name}' array in npz file"))?
+        .read_to_end(&mut bytes)?;
+
+    if !bytes.starts_with(NPY_MAGIC) || bytes.len() < NPY_MAGIC.len() + 4 {
+        return Err(format!("'{name}' is not an npy array").into());
+    }
+    let (dict_len, dict_start) = match bytes[NPY_MAGIC.len()] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize, 12),
+        version => return Err(format!("Unsupported npy version {version} in '{name}'").into()),
+    };
+    let malformed = || format!("Malformed npy header in '{name}'");
+    let dict = bytes
+        .get(dict_start..dict_start + dict_len)
+        .ok_or_else(|| format!("Truncated npy header in '{name}'"))?;
+    let dict = std::str::from_utf8(dict).map_err(|_| malformed())?;
+
+    let descr = dict_value(dict, "descr")
+        .and_then(|d| d.strip_prefix('\'')?.split('\'').next())
+        .ok_or_else(malformed)?
+        .to_string();
+    let shape = dict_value(dict, "shape")
+        .and_then(|s| s.strip_prefix('(')?.split(')').next())
+        .ok_or_else(malformed)?
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().map_err(|_| malformed().into()))
+        .collect::<Result<Vec<usize>>>()?;
+
+    let data = bytes.split_off(dict_start + dict_len);
+    let item_size: usize = descr.get(2..).and_then(|s| s.parse().ok()).ok_or_else(malformed)?;
+    if descr.starts_with('>') || data.len() != shape.iter().product::<usize>() * item_size {
+        return Err(format!("Unsupported or truncated data in '{name}'").into());
+    }
+
+    Ok(Npy { descr, shape, data })
+}
+
+/// The text after `'key':` in a header dict.
+fn dict_value<'a>(dict: &'a str, key: &str) -> Option<&'a str> {
+    let start = dict.find(&format!("'{key}':"))? + key.len() + 3;
+    Some(dict[start..].trim_start())
+}
+
+/// Reads a 1-D array of non-negative integers, as int32 or int64.
+fn read_indices(npy: Npy) -> Result<Vec<usize>> {
+    if npy.shape.len() != 1 {
+        return Err("Index arrays must have one dimension".into());
+    }
+    let idxs: Vec<i64> = match npy.descr.as_str() {
+        "<i4" => npy.data.chunks_exact(4).map(|b| i32::read_le(b) as i64).collect(),
+        "<i8" => npy.data.chunks_exact(8).map(i64::read_le).collect(),
+        descr => return Err(format!("Unsupported index dtype '{descr}' in npz file").into()),
+    };
+    idxs.into_iter()
+        .map(|i| usize::try_from(i).map_err(|_| "Negative index in npz file".into()))
+        .collect()
+}
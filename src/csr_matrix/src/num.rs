@@ -0,0 +1,117 @@
+use bytemuck::Pod;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, Sub};
+
+/// Element type that can be stored in a sparse matrix.
+///
+/// Values are plain old data, so stored arrays can be viewed as bytes and
+/// back, see [`crate::view`].
+pub trait Value:
+    Pod
+    + Debug
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + AddAssign
+    + Send
+    + Sync
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn to_f64(self) -> f64;
+    fn from_f64(v: f64) -> Self;
+
+    /// `self - rhs`, or `None` if the result does not fit in `Self`.
+    /// Floats never fail.
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+
+    /// A total order, which for floats is IEEE 754 `totalOrder`, so NaN
+    /// sorts above every number.
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+/// Integer type used for column indices and row pointers.
+///
+/// Indices are converted to and from `usize` at the API boundary, so a
+/// narrower type only affects how the matrix is stored.
+pub trait Index: Pod + Debug + Default + Ord + Send + Sync + 'static {
+    fn from_usize(i: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+macro_rules! impl_value_int {
+    ($($t:ty),*) => {$(
+        impl Value for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64(v: f64) -> Self {
+                v as $t
+            }
+
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_sub(self, rhs)
+            }
+
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                self.cmp(other)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_value_float {
+    ($($t:ty),*) => {$(
+        impl Value for $t {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f64(v: f64) -> Self {
+                v as $t
+            }
+
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                Some(self - rhs)
+            }
+
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                <$t>::total_cmp(self, other)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_index {
+    ($($t:ty),*) => {$(
+        impl Index for $t {
+            #[inline]
+            fn from_usize(i: usize) -> Self {
+                <$t>::try_from(i).expect(concat!("index does not fit in ", stringify!($t)))
+            }
+
+            #[inline]
+            fn index(self) -> usize {
+                self as usize
+            }
+        }
+    )*};
+}
+
+impl_value_int!(u32, u64, i32, i64);
+impl_value_float!(f32, f64);
+impl_index!(u16, u32, u64, usize);
@@ -1,67 +1,65 @@
-use crate::CSR;
-use std::error::Error;
+use crate::{Error, Index, Result, Value, CSR};
 
-use std::ops::Add;
+use std::ops::{Add, Div, Mul, Sub};
 
-impl Add for &CSR {
-    type Output = Result<CSR, Box<dyn Error>>;
+impl<V: Value, I: Index> Add for &CSR<V, I> {
+    type Output = Result<CSR<V, I>>;
 
-    fn add(self, other: &CSR) -> Self::Output {
-        if self.n_rows != other.n_rows || self.n_cols != other.n_cols {
-            return Err("Matrices are not the same size".into());
+    fn add(self, other: &CSR<V, I>) -> Self::Output {
+        check_same_size(self, other)?;
+        Ok(merge(self, other, true, |a, b| a + b))
+    }
+}
+
+impl<V: Value, I: Index> Mul for &CSR<V, I> {
+    type Output = Result<CSR<V, I>>;
+
+    /// Sparse matrix product (Gustavson's row-by-row algorithm).
+    fn mul(self, other: &CSR<V, I>) -> Self::Output {
+        if self.n_cols != other.n_rows {
+            return Err(Error::DimensionMismatch {
+                lhs: (self.n_rows, self.n_cols),
+                rhs: (other.n_rows, other.n_cols),
+            });
         }
 
-        let mut row_ptr = Vec::with_capacity(other.n_rows + 1);
+        let mut row_ptr = Vec::with_capacity(self.n_rows + 1);
         let mut col_idx = Vec::new();
         let mut val = Vec::new();
 
-        row_ptr.push(0);
+        // dense accumulator for the current row, `marker[j] == i` means
+        // column j has already been touched while computing row i
+        let mut acc = vec![V::ZERO; other.n_cols];
+        let mut marker = vec![usize::MAX; other.n_cols];
+        let mut row_cols: Vec<usize> = Vec::new();
+
+        row_ptr.push(I::default());
         for i in 0..self.n_rows {
-            let lhs_row_start = self.row_ptr[i];
-            let lhs_row_end = self.row_ptr[i + 1];
-            let rhs_row_start = other.row_ptr[i];
-            let rhs_row_end = other.row_ptr[i + 1];
-
-            let mut lhs_i = lhs_row_start;
-            let mut rhs_i = rhs_row_start;
-            while lhs_i < lhs_row_end || rhs_i < rhs_row_end {
-                if lhs_i == lhs_row_end {
-                    col_idx.push(other.col_idx[rhs_i]);
-                    val.push(other.val[rhs_i]);
-                    rhs_i += 1;
-                } else if rhs_i == rhs_row_end {
-                    col_idx.push(self.col_idx[lhs_i]);
-                    val.push(self.val[lhs_i]);
-                    lhs_i += 1;
-                } else {
-                    let lhs_col = self.col_idx[lhs_i];
-                    let rhs_col = other.col_idx[rhs_i];
-
-                    if lhs_col == rhs_col {
-                        let sum = self.val[lhs_i] + other.val[rhs_i];
-                        if sum != 0.0 {
-                            val.push(self.val[lhs_i] + other.val[rhs_i]);
-                            col_idx.push(lhs_col);
-                            lhs_i += 1;
-                            rhs_i += 1;
-                        }
-                    } else if lhs_col < rhs_col {
-                        val.push(self.val[lhs_i]);
-                        col_idx.push(lhs_col);
-                        lhs_i += 1;
-                    } else if lhs_col > rhs_col {
-                        val.push(other.val[rhs_i]);
-                        col_idx.push(rhs_col);
-                        rhs_i += 1;
+            for (k, lhs_val) in self.inter_row(i) {
+                for (j, rhs_val) in other.inter_row(k) {
+                    if marker[j] != i {
+                        marker[j] = i;
+                        acc[j] = V::ZERO;
+                        row_cols.push(j);
                     }
+                    acc[j] += lhs_val * rhs_val;
                 }
             }
-            row_ptr.push(val.len());
+
+            row_cols.sort_unstable();
+            for &j in &row_cols {
+                if acc[j] != V::ZERO {
+                    col_idx.push(I::from_usize(j));
+                    val.push(acc[j]);
+                }
+            }
+            row_cols.clear();
+            row_ptr.push(I::from_usize(val.len()));
         }
 
         Ok(CSR {
             n_rows: self.n_rows,
-            n_cols: self.n_cols,
+            n_cols: other.n_cols,
             n_nz: val.len(),
             row_ptr,
             col_idx,
@@ -69,3 +67,221 @@ impl Add for &CSR {
         })
     }
 }
+
+impl<V: Value, I: Index> Mul<&[V]> for &CSR<V, I> {
+    type Output = Result<Vec<V>>;
+
+    fn mul(self, x: &[V]) -> Self::Output {
+        self.mul_vec(x)
+    }
+}
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// Computes `A * x` for a dense vector `x` of length `n_cols`.
+    pub fn mul_vec(&self, x: &[V]) -> Result<Vec<V>> {
+        if x.len() != self.n_cols {
+            return Err(Error::DimensionMismatch {
+                lhs: (self.n_rows, self.n_cols),
+                rhs: (x.len(), 1),
+            });
+        }
+
+        let y = (0..self.n_rows)
+            .map(|i| self.inter_row(i).fold(V::ZERO, |acc, (j, v)| acc + v * x[j]))
+            .collect();
+
+        Ok(y)
+    }
+
+    /// Computes `A^T * x` for a dense vector `x` of length `n_rows`, without
+    /// materializing the transpose.
+    pub fn transpose_mul_vec(&self, x: &[V]) -> Result<Vec<V>> {
+        if x.len() != self.n_rows {
+            return Err(Error::DimensionMismatch {
+                lhs: (self.n_cols, self.n_rows),
+                rhs: (x.len(), 1),
+            });
+        }
+
+        let mut y = vec![V::ZERO; self.n_cols];
+        for (i, j, v) in self {
+            y[j] += v * x[i];
+        }
+
+        Ok(y)
+    }
+}
+
+impl<V: Value, I: Index> Sub for &CSR<V, I> {
+    type Output = Result<CSR<V, I>>;
+
+    /// Element-wise difference. Fails with [`Error::Underflow`] if a cell
+    /// does not fit in `V`, which for unsigned values is any cell where
+    /// `other` is larger, including cells `self` does not store.
+    fn sub(self, other: &CSR<V, I>) -> Self::Output {
+        check_same_size(self, other)?;
+        // `a - 0` always fits, so only cells stored in `other` can fail
+        for (row, col, b) in other {
+            let a = self.value(row, col).unwrap_or(V::ZERO);
+            if a.checked_sub(b).is_none() {
+                return Err(Error::Underflow { row, col });
+            }
+        }
+        Ok(merge(self, other, true, |a, b| a - b))
+    }
+}
+
+impl<V: Value, I: Index> Mul<V> for &CSR<V, I> {
+    type Output = CSR<V, I>;
+
+    fn mul(self, scalar: V) -> Self::Output {
+        self.map_values(|v| v * scalar)
+    }
+}
+
+impl<V: Value, I: Index> Div<V> for &CSR<V, I> {
+    type Output = CSR<V, I>;
+
+    fn div(self, scalar: V) -> Self::Output {
+        self.map_values(|v| v / scalar)
+    }
+}
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// Element-wise (Hadamard) product.
+    pub fn hadamard(&self, other: &CSR<V, I>) -> Result<CSR<V, I>> {
+        check_same_size(self, other)?;
+        Ok(merge(self, other, false, |a, b| a * b))
+    }
+
+    /// Element-wise division over the cells stored in both matrices. Cells
+    /// missing from `other` would divide by zero and are dropped instead.
+    pub fn div_elementwise(&self, other: &CSR<V, I>) -> Result<CSR<V, I>> {
+        check_same_size(self, other)?;
+        Ok(merge(self, other, false, |a, b| a / b))
+    }
+
+    /// Applies `f` to every stored value, dropping results that are zero.
+    pub fn map_values<W, F>(&self, f: F) -> CSR<W, I>
+    where
+        W: Value,
+        F: Fn(V) -> W,
+    {
+        self.map_entries(|_, _, v| f(v))
+    }
+
+    /// Like [`CSR::map_values`] but `f` also receives the row and column.
+    pub fn map_entries<W, F>(&self, f: F) -> CSR<W, I>
+    where
+        W: Value,
+        F: Fn(usize, usize, V) -> W,
+    {
+        let mut row_ptr = Vec::with_capacity(self.n_rows + 1);
+        let mut col_idx = Vec::with_capacity(self.n_nz);
+        let mut val = Vec::with_capacity(self.n_nz);
+
+        row_ptr.push(I::default());
+        for row in 0..self.n_rows {
+            for k in self.row_ptr[row].index()..self.row_ptr[row + 1].index() {
+                let v = f(row, self.col_idx[k].index(), self.val[k]);
+                if v != W::ZERO {
+                    col_idx.push(self.col_idx[k]);
+                    val.push(v);
+                }
+            }
+            row_ptr.push(I::from_usize(val.len()));
+        }
+
+        CSR {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            n_nz: val.len(),
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+
+    /// Combines two matrices cell by cell over the union of their stored
+    /// cells; a cell missing from one side is passed to `f` as zero.
+    pub fn zip_map<U, W, F>(&self, other: &CSR<U, I>, f: F) -> Result<CSR<W, I>>
+    where
+        U: Value,
+        W: Value,
+        F: Fn(V, U) -> W,
+    {
+        check_same_size(self, other)?;
+        Ok(merge(self, other, true, f))
+    }
+}
+
+fn check_same_size<V, U, I>(lhs: &CSR<V, I>, rhs: &CSR<U, I>) -> Result<()> {
+    if lhs.n_rows != rhs.n_rows || lhs.n_cols != rhs.n_cols {
+        return Err(Error::DimensionMismatch {
+            lhs: (lhs.n_rows, lhs.n_cols),
+            rhs: (rhs.n_rows, rhs.n_cols),
+        });
+    }
+    Ok(())
+}
+
+/// Row-by-row two-pointer merge of `lhs` and `rhs`. With `union` every cell
+/// stored in either side is visited, otherwise only cells stored in both.
+/// Results equal to zero are not stored.
+fn merge<V, U, W, I, F>(lhs: &CSR<V, I>, rhs: &CSR<U, I>, union: bool, f: F) -> CSR<W, I>
+where
+    V: Value,
+    U: Value,
+    W: Value,
+    I: Index,
+    F: Fn(V, U) -> W,
+{
+    let mut row_ptr = Vec::with_capacity(lhs.n_rows + 1);
+    let mut col_idx = Vec::new();
+    let mut val = Vec::new();
+
+    row_ptr.push(I::default());
+    for row in 0..lhs.n_rows {
+        let mut lhs_i = lhs.row_ptr[row].index();
+        let lhs_end = lhs.row_ptr[row + 1].index();
+        let mut rhs_i = rhs.row_ptr[row].index();
+        let rhs_end = rhs.row_ptr[row + 1].index();
+
+        while lhs_i < lhs_end || rhs_i < rhs_end {
+            let take_lhs = rhs_i == rhs_end
+                || (lhs_i < lhs_end && lhs.col_idx[lhs_i] <= rhs.col_idx[rhs_i]);
+            let take_rhs = lhs_i == lhs_end
+                || (rhs_i < rhs_end && rhs.col_idx[rhs_i] <= lhs.col_idx[lhs_i]);
+
+            let (col, v) = if take_lhs && take_rhs {
+                let cell = (lhs.col_idx[lhs_i], Some(f(lhs.val[lhs_i], rhs.val[rhs_i])));
+                lhs_i += 1;
+                rhs_i += 1;
+                cell
+            } else if take_lhs {
+                let cell = (lhs.col_idx[lhs_i], union.then(|| f(lhs.val[lhs_i], U::ZERO)));
+                lhs_i += 1;
+                cell
+            } else {
+                let cell = (rhs.col_idx[rhs_i], union.then(|| f(V::ZERO, rhs.val[rhs_i])));
+                rhs_i += 1;
+                cell
+            };
+
+            if let Some(v) = v.filter(|&v| v != W::ZERO) {
+                col_idx.push(col);
+                val.push(v);
+            }
+        }
+        row_ptr.push(I::from_usize(val.len()));
+    }
+
+    CSR {
+        n_rows: lhs.n_rows,
+        n_cols: lhs.n_cols,
+        n_nz: val.len(),
+        row_ptr,
+        col_idx,
+        val,
+    }
+}
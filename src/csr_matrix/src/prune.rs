@@ -0,0 +1,70 @@
+use crate::{Index, Value, CSR};
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// Drops every entry smaller than `min`.
+    pub fn prune_threshold(&self, min: V) -> CSR<V, I> {
+        self.prune(min, None)
+    }
+
+    /// Keeps the `k` largest entries of every row. Ties are broken towards
+    /// the lower column so the result is deterministic.
+    pub fn top_k_per_row(&self, k: usize) -> CSR<V, I> {
+        self.prune_with(|_| true, Some(k))
+    }
+
+    /// Drops entries smaller than `min`, then keeps at most `k` of the
+    /// remaining entries per row (all of them when `k` is `None`).
+    pub fn prune(&self, min: V, k: Option<usize>) -> CSR<V, I> {
+        self.prune_with(|v| v >= min, k)
+    }
+
+    fn prune_with<F>(&self, keep: F, k: Option<usize>) -> CSR<V, I>
+    where
+        F: Fn(V) -> bool,
+    {
+        let mut row_ptr = Vec::with_capacity(self.n_rows + 1);
+        let mut col_idx = Vec::new();
+        let mut val = Vec::new();
+
+        // positions in `col_idx`/`val` of the entries kept for one row
+        let mut kept: Vec<usize> = Vec::new();
+
+        row_ptr.push(I::default());
+        for row in 0..self.n_rows {
+            kept.clear();
+            kept.extend(
+                (self.row_ptr[row].index()..self.row_ptr[row + 1].index())
+                    .filter(|&i| keep(self.val[i])),
+            );
+
+            if let Some(k) = k.filter(|&k| k < kept.len()) {
+                let by_value_desc = |&a: &usize, &b: &usize| {
+                    self.val[b].total_cmp(&self.val[a]).then(a.cmp(&b))
+                };
+                if k == 0 {
+                    kept.clear();
+                } else {
+                    kept.select_nth_unstable_by(k - 1, by_value_desc);
+                    kept.truncate(k);
+                    // positions are ordered like columns within a row
+                    kept.sort_unstable();
+                }
+            }
+
+            for &i in &kept {
+                col_idx.push(self.col_idx[i]);
+                val.push(self.val[i]);
+            }
+            row_ptr.push(I::from_usize(val.len()));
+        }
+
+        CSR {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            n_nz: val.len(),
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+}
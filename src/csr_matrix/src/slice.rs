@@ -0,0 +1,126 @@
+use crate::{Index, Value, CSR};
+
+/// Maps each old index to its new index, or `None` if it was dropped.
+pub type IndexMap = Vec<Option<usize>>;
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// New row `i` is old row `rows[i]`; rows may repeat.
+    pub fn select_rows(&self, rows: &[usize]) -> CSR<V, I> {
+        self.select(rows, None, self.n_cols)
+    }
+
+    /// New column `j` is old column `cols[j]`; columns must be unique.
+    pub fn select_cols(&self, cols: &[usize]) -> CSR<V, I> {
+        let col_map = self.col_map(cols);
+        let all_rows: Vec<usize> = (0..self.n_rows).collect();
+        self.select(&all_rows, Some(&col_map), cols.len())
+    }
+
+    /// Selects rows and columns at once, see [`CSR::select_rows`] and
+    /// [`CSR::select_cols`].
+    pub fn submatrix(&self, rows: &[usize], cols: &[usize]) -> CSR<V, I> {
+        let col_map = self.col_map(cols);
+        self.select(rows, Some(&col_map), cols.len())
+    }
+
+    /// Drops empty rows and empty columns, keeping the remaining ones in
+    /// order, and returns the row and column maps.
+    pub fn compact(&self) -> (CSR<V, I>, IndexMap, IndexMap) {
+        let mut row_used = vec![false; self.n_rows];
+        let mut col_used = vec![false; self.n_cols];
+        for (row, col, _) in self {
+            row_used[row] = true;
+            col_used[col] = true;
+        }
+
+        let rows = kept(&row_used);
+        let cols = kept(&col_used);
+        let compacted = self.submatrix(&rows, &cols);
+
+        (compacted, index_map(&row_used), index_map(&col_used))
+    }
+
+    /// Like [`CSR::compact`] for square matrices whose rows and columns share
+    /// one index space: index `i` is kept if row `i` or column `i` is
+    /// non-empty, and the same map applies to both axes.
+    pub fn compact_square(&self) -> (CSR<V, I>, IndexMap) {
+        assert_eq!(self.n_rows, self.n_cols, "matrix must be square");
+
+        let mut used = vec![false; self.n_rows];
+        for (row, col, _) in self {
+            used[row] = true;
+            used[col] = true;
+        }
+
+        let idxs = kept(&used);
+        (self.submatrix(&idxs, &idxs), index_map(&used))
+    }
+
+    fn col_map(&self, cols: &[usize]) -> IndexMap {
+        let mut col_map = vec![None; self.n_cols];
+        for (new, &old) in cols.iter().enumerate() {
+            assert!(old < self.n_cols, "column {old} out of bounds");
+            assert!(col_map[old].is_none(), "column {old} selected twice");
+            col_map[old] = Some(new);
+        }
+        col_map
+    }
+
+    fn select(&self, rows: &[usize], col_map: Option<&[Option<usize>]>, n_cols: usize) -> CSR<V, I> {
+        let mut row_ptr = Vec::with_capacity(rows.len() + 1);
+        let mut col_idx = Vec::new();
+        let mut val = Vec::new();
+        let mut scratch: Vec<(usize, V)> = Vec::new();
+
+        row_ptr.push(I::default());
+        for &row in rows {
+            assert!(row < self.n_rows, "row {row} out of bounds");
+            match col_map {
+                None => {
+                    let row_start = self.row_ptr[row].index();
+                    let row_end = self.row_ptr[row + 1].index();
+                    col_idx.extend_from_slice(&self.col_idx[row_start..row_end]);
+                    val.extend_from_slice(&self.val[row_start..row_end]);
+                }
+                Some(col_map) => {
+                    scratch.clear();
+                    scratch.extend(
+                        self.inter_row(row)
+                            .filter_map(|(col, v)| col_map[col].map(|new| (new, v))),
+                    );
+                    scratch.sort_unstable_by_key(|&(col, _)| col);
+                    for &(col, v) in &scratch {
+                        col_idx.push(I::from_usize(col));
+                        val.push(v);
+                    }
+                }
+            }
+            row_ptr.push(I::from_usize(val.len()));
+        }
+
+        CSR {
+            n_rows: rows.len(),
+            n_cols,
+            n_nz: val.len(),
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+}
+
+fn kept(used: &[bool]) -> Vec<usize> {
+    (0..used.len()).filter(|&i| used[i]).collect()
+}
+
+fn index_map(used: &[bool]) -> IndexMap {
+    let mut next = 0;
+    used.iter()
+        .map(|&u| {
+            u.then(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect()
+}
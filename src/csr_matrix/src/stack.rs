@@ -0,0 +1,182 @@
+use crate::{Error, Index, Result, Value, CSR};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+impl<V: Value, I: Index> CSR<V, I> {
+    /// Stacks matrices on top of each other; all must have the same number
+    /// of columns.
+    pub fn vstack(mats: &[&CSR<V, I>]) -> Result<CSR<V, I>> {
+        let first = mats.first().ok_or(Error::NoMatrices)?;
+        if let Some(m) = mats.iter().find(|m| m.n_cols != first.n_cols) {
+            return Err(mismatch(first, m));
+        }
+
+        let n_rows = mats.iter().map(|m| m.n_rows).sum();
+        let n_nz = mats.iter().map(|m| m.n_nz).sum();
+        let mut row_ptr = Vec::with_capacity(n_rows + 1);
+        let mut col_idx = Vec::with_capacity(n_nz);
+        let mut val = Vec::with_capacity(n_nz);
+
+        row_ptr.push(I::default());
+        for m in mats {
+            let offset = col_idx.len();
+            row_ptr.extend(m.row_ptr[1..].iter().map(|p| I::from_usize(p.index() + offset)));
+            col_idx.extend_from_slice(&m.col_idx);
+            val.extend_from_slice(&m.val);
+        }
+
+        Ok(CSR {
+            n_rows,
+            n_cols: first.n_cols,
+            n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        })
+    }
+
+    /// Places matrices side by side; all must have the same number of rows.
+    pub fn hstack(mats: &[&CSR<V, I>]) -> Result<CSR<V, I>> {
+        let first = mats.first().ok_or(Error::NoMatrices)?;
+        if let Some(m) = mats.iter().find(|m| m.n_rows != first.n_rows) {
+            return Err(mismatch(first, m));
+        }
+
+        let n_cols = mats.iter().map(|m| m.n_cols).sum();
+        let n_nz = mats.iter().map(|m| m.n_nz).sum();
+        let mut row_ptr = Vec::with_capacity(first.n_rows + 1);
+        let mut col_idx = Vec::with_capacity(n_nz);
+        let mut val = Vec::with_capacity(n_nz);
+
+        row_ptr.push(I::default());
+        for row in 0..first.n_rows {
+            let mut col_offset = 0;
+            for m in mats {
+                for (col, v) in m.inter_row(row) {
+                    col_idx.push(I::from_usize(col + col_offset));
+                    val.push(v);
+                }
+                col_offset += m.n_cols;
+            }
+            row_ptr.push(I::from_usize(val.len()));
+        }
+
+        Ok(CSR {
+            n_rows: first.n_rows,
+            n_cols,
+            n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        })
+    }
+
+    /// Places matrices along the diagonal of an otherwise empty matrix.
+    pub fn block_diag(mats: &[&CSR<V, I>]) -> CSR<V, I> {
+        let n_rows = mats.iter().map(|m| m.n_rows).sum();
+        let n_cols = mats.iter().map(|m| m.n_cols).sum();
+        let n_nz = mats.iter().map(|m| m.n_nz).sum();
+        let mut row_ptr = Vec::with_capacity(n_rows + 1);
+        let mut col_idx = Vec::with_capacity(n_nz);
+        let mut val = Vec::with_capacity(n_nz);
+
+        row_ptr.push(I::default());
+        let mut col_offset = 0;
+        for m in mats {
+            let nz_offset = col_idx.len();
+            row_ptr.extend(m.row_ptr[1..].iter().map(|p| I::from_usize(p.index() + nz_offset)));
+            col_idx.extend(m.col_idx.iter().map(|c| I::from_usize(c.index() + col_offset)));
+            val.extend_from_slice(&m.val);
+            col_offset += m.n_cols;
+        }
+
+        CSR {
+            n_rows,
+            n_cols,
+            n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        }
+    }
+
+    /// Sums many same-sized matrices in a single k-way merge per row, instead
+    /// of one full pass per matrix with repeated [`CSR::add_in_place`].
+    pub fn merge_add(mats: &[&CSR<V, I>]) -> Result<CSR<V, I>> {
+        let first = mats.first().ok_or(Error::NoMatrices)?;
+        if let Some(m) = mats
+            .iter()
+            .find(|m| m.n_rows != first.n_rows || m.n_cols != first.n_cols)
+        {
+            return Err(mismatch(first, m));
+        }
+
+        let mut row_ptr = Vec::with_capacity(first.n_rows + 1);
+        let mut col_idx = Vec::with_capacity(mats.iter().map(|m| m.n_nz).max().unwrap_or(0));
+        let mut val = Vec::with_capacity(col_idx.capacity());
+
+        // heap of (next column, matrix) across the current row of every matrix
+        let mut heap: BinaryHeap<Reverse<(I, usize)>> = BinaryHeap::with_capacity(mats.len());
+        let mut pos = vec![0; mats.len()];
+        let mut end = vec![0; mats.len()];
+
+        row_ptr.push(I::default());
+        for row in 0..first.n_rows {
+            for (k, m) in mats.iter().enumerate() {
+                pos[k] = m.row_ptr[row].index();
+                end[k] = m.row_ptr[row + 1].index();
+                if pos[k] < end[k] {
+                    heap.push(Reverse((m.col_idx[pos[k]], k)));
+                }
+            }
+
+            while let Some(Reverse((col, k))) = heap.pop() {
+                let mut sum = mats[k].val[pos[k]];
+                advance(mats[k], k, &mut pos, &end, &mut heap);
+                while let Some(&Reverse((next_col, next_k))) = heap.peek() {
+                    if next_col != col {
+                        break;
+                    }
+                    heap.pop();
+                    sum += mats[next_k].val[pos[next_k]];
+                    advance(mats[next_k], next_k, &mut pos, &end, &mut heap);
+                }
+
+                if sum != V::ZERO {
+                    col_idx.push(col);
+                    val.push(sum);
+                }
+            }
+            row_ptr.push(I::from_usize(val.len()));
+        }
+
+        Ok(CSR {
+            n_rows: first.n_rows,
+            n_cols: first.n_cols,
+            n_nz: val.len(),
+            row_ptr,
+            col_idx,
+            val,
+        })
+    }
+}
+
+fn mismatch<V, I>(lhs: &CSR<V, I>, rhs: &CSR<V, I>) -> Error {
+    Error::DimensionMismatch {
+        lhs: (lhs.n_rows, lhs.n_cols),
+        rhs: (rhs.n_rows, rhs.n_cols),
+    }
+}
+
+fn advance<V, I: Index>(
+    m: &CSR<V, I>,
+    k: usize,
+    pos: &mut [usize],
+    end: &[usize],
+    heap: &mut BinaryHeap<Reverse<(I, usize)>>,
+) {
+    pos[k] += 1;
+    if pos[k] < end[k] {
+        heap.push(Reverse((m.col_idx[pos[k]], k)));
+    }
+}
@@ -0,0 +1,410 @@
+use crate::iter::{CSRIter, CSRRowIter};
+use crate::{CSRView, Error, Index, Result, Value, CSR};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::{Add, Range};
+
+/// Symmetric square matrix that stores only its upper triangle (diagonal
+/// included), halving serialized size compared to a full [`CSR`].
+///
+/// Row access still returns the full row: the part right of the diagonal is
+/// read directly, the part left of it through a [`ColumnIndex`] of the upper
+/// triangle, so [`SymmetricCSR::inter_row`] costs O(nnz in row log n). The
+/// index is rebuilt when deserialized, but written out by
+/// [`SymmetricCSR::write_aligned`] so an aligned view need not build it.
+#[derive(Debug, Clone)]
+pub struct SymmetricCSR<V = f32, I = usize> {
+    upper: CSR<V, I>,
+    pub(crate) index: ColumnIndex<I>,
+}
+
+/// Where the entries above the diagonal of an upper triangle are, column by
+/// column, so its rows can be read in full. Holds one position per entry
+/// above the diagonal.
+#[derive(Debug, Clone)]
+pub struct ColumnIndex<I = usize> {
+    /// Start in `below` of each column's entries above the diagonal.
+    pub(crate) col_ptr: Vec<I>,
+    /// Positions in the upper triangle of the entries above the diagonal, by
+    /// column and then row.
+    pub(crate) below: Vec<I>,
+}
+
+/// A [`ColumnIndex`] borrowing its arrays, e.g. out of a memory-mapped file
+/// with [`SymmetricCSRView::from_aligned`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnIndexView<'a, I = usize> {
+    pub(crate) col_ptr: &'a [I],
+    pub(crate) below: &'a [I],
+}
+
+impl<I: Index> ColumnIndexView<'_, I> {
+    pub fn to_column_index(&self) -> ColumnIndex<I> {
+        ColumnIndex {
+            col_ptr: self.col_ptr.to_vec(),
+            below: self.below.to_vec(),
+        }
+    }
+}
+
+impl<I: Index> ColumnIndex<I> {
+    /// Indexes `upper`, failing if its arrays are inconsistent as
+    /// [`CSR::try_from_parts`] checks, or it is not square or has entries
+    /// below the diagonal.
+    pub fn new<V: Value>(upper: CSRView<'_, V, I>) -> Result<ColumnIndex<I>> {
+        upper.check()?;
+        if upper.n_rows != upper.n_cols {
+            return Err(Error::NotSquare {
+                n_rows: upper.n_rows,
+                n_cols: upper.n_cols,
+            });
+        }
+        for row in 0..upper.n_rows {
+            let row_start = upper.row_ptr[row].index();
+            if upper.row_ptr[row + 1].index() > row_start && upper.col_idx[row_start].index() < row {
+                return Err(Error::NotUpperTriangular);
+            }
+        }
+
+        Ok(ColumnIndex::build(upper))
+    }
+
+    /// Indexes a square upper triangle.
+    fn build<V: Value>(upper: CSRView<'_, V, I>) -> ColumnIndex<I> {
+        let n = upper.n_cols;
+        let mut counts = vec![0usize; n + 1];
+        for row in 0..upper.n_rows {
+            for (col, _) in upper.inter_row(row) {
+                if row < col {
+                    counts[col + 1] += 1;
+                }
+            }
+        }
+        for col in 0..n {
+            counts[col + 1] += counts[col];
+        }
+        let col_ptr = counts.iter().map(|&p| I::from_usize(p)).collect();
+
+        // rows are visited in order, so each column ends up sorted by row
+        let mut below = vec![I::default(); counts[n]];
+        for row in 0..upper.n_rows {
+            for pos in upper.row_ptr[row].index()..upper.row_ptr[row + 1].index() {
+                let col = upper.col_idx[pos].index();
+                if row < col {
+                    below[counts[col]] = I::from_usize(pos);
+                    counts[col] += 1;
+                }
+            }
+        }
+
+        ColumnIndex { col_ptr, below }
+    }
+
+    pub fn view(&self) -> ColumnIndexView<'_, I> {
+        ColumnIndexView {
+            col_ptr: &self.col_ptr,
+            below: &self.below,
+        }
+    }
+}
+
+/// A [`SymmetricCSR`] borrowing its upper triangle and column index, e.g.
+/// both viewed out of a memory-mapped file with
+/// [`SymmetricCSRView::from_aligned`].
+#[derive(Debug, Clone, Copy)]
+pub struct SymmetricCSRView<'a, V = f32, I = usize> {
+    upper: CSRView<'a, V, I>,
+    index: ColumnIndexView<'a, I>,
+}
+
+impl<'a, V: Value, I: Index> SymmetricCSRView<'a, V, I> {
+    /// Views `upper` through `index`, which must have been built from it;
+    /// any other index gives wrong rows or panics.
+    pub fn new(upper: CSRView<'a, V, I>, index: ColumnIndexView<'a, I>) -> SymmetricCSRView<'a, V, I> {
+        SymmetricCSRView { upper, index }
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.upper.n_rows
+    }
+
+    pub fn n_cols(&self) -> usize {
+        self.upper.n_cols
+    }
+
+    /// Number of entries actually stored, i.e. in the upper triangle.
+    pub fn n_stored(&self) -> usize {
+        self.upper.n_nz
+    }
+
+    pub fn upper(&self) -> CSRView<'a, V, I> {
+        self.upper
+    }
+
+    pub fn index(&self) -> ColumnIndexView<'a, I> {
+        self.index
+    }
+
+    pub fn value(&self, row: usize, col: usize) -> Option<V> {
+        self.upper.value(row.min(col), row.max(col))
+    }
+
+    /// Iterates over the full row as `(col, value)` with columns ascending.
+    pub fn inter_row(&self, row: usize) -> SymmetricRowIter<'a, V, I> {
+        let below = if row < self.n_rows() {
+            self.index.col_ptr[row].index()..self.index.col_ptr[row + 1].index()
+        } else {
+            0..0
+        };
+        SymmetricRowIter {
+            upper: self.upper,
+            index: self.index,
+            below,
+            row: self.upper.inter_row(row),
+        }
+    }
+
+    pub fn to_symmetric_csr(&self) -> SymmetricCSR<V, I> {
+        SymmetricCSR {
+            upper: self.upper.to_csr(),
+            index: self.index.to_column_index(),
+        }
+    }
+}
+
+/// How a [`SymmetricCSR`] is serialized: its upper triangle only.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "SymmetricCSR")]
+struct StoredUpper<U> {
+    upper: U,
+}
+
+impl<V: Value + Serialize, I: Index + Serialize> Serialize for SymmetricCSR<V, I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        StoredUpper { upper: &self.upper }.serialize(serializer)
+    }
+}
+
+impl<'de, V, I> Deserialize<'de> for SymmetricCSR<V, I>
+where
+    V: Value + Deserialize<'de>,
+    I: Index + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let stored = StoredUpper::<CSR<V, I>>::deserialize(deserializer)?;
+        SymmetricCSR::from_upper(stored.upper).map_err(serde::de::Error::custom)
+    }
+}
+
+impl<V: Value, I: Index> SymmetricCSR<V, I> {
+    pub fn new(n: usize) -> SymmetricCSR<V, I> {
+        SymmetricCSR::indexed(CSR::new(n, n))
+    }
+
+    /// Wraps a square upper triangle, building its column index.
+    fn indexed(upper: CSR<V, I>) -> SymmetricCSR<V, I> {
+        let index = ColumnIndex::build(upper.view());
+        SymmetricCSR { upper, index }
+    }
+
+    /// Wraps a matrix that already holds only upper-triangle entries.
+    pub fn from_upper(upper: CSR<V, I>) -> Result<SymmetricCSR<V, I>> {
+        let index = ColumnIndex::new(upper.view())?;
+        Ok(SymmetricCSR { upper, index })
+    }
+
+    pub fn view(&self) -> SymmetricCSRView<'_, V, I> {
+        SymmetricCSRView::new(self.upper.view(), self.index.view())
+    }
+
+    /// Keeps the upper triangle of `full`, failing if `full` is not symmetric.
+    pub fn from_csr(full: &CSR<V, I>) -> Result<SymmetricCSR<V, I>> {
+        if full.n_rows != full.n_cols {
+            return Err(Error::NotSquare {
+                n_rows: full.n_rows,
+                n_cols: full.n_cols,
+            });
+        }
+
+        let mut triples = Vec::with_capacity(full.n_nz / 2 + full.n_rows);
+        for (row, col, val) in full {
+            if full.value(col, row) != Some(val) {
+                return Err(Error::NotSymmetric);
+            }
+            if row <= col {
+                triples.push((row, col, val));
+            }
+        }
+
+        Ok(SymmetricCSR::indexed(CSR::from_triples(&triples, full.n_rows, full.n_cols)))
+    }
+
+    /// Builds from entries given for either triangle. `(r, c)` and `(c, r)`
+    /// name the same cell, and the values of repeated cells are summed.
+    pub fn from_triples(triplets: &[(usize, usize, V)], n: usize) -> SymmetricCSR<V, I> {
+        let upper_triples: Vec<_> = triplets
+            .iter()
+            .map(|&(r, c, v)| (r.min(c), r.max(c), v))
+            .collect();
+
+        SymmetricCSR::indexed(CSR::from_triples(&upper_triples, n, n))
+    }
+
+    /// Builds from a map keyed by index pairs in either order, as
+    /// [`SymmetricCSR::from_triples`]: the values of `(r, c)` and `(c, r)`
+    /// are summed.
+    pub fn from_fxhash(fxhash: &FxHashMap<(usize, usize), V>, n: usize) -> SymmetricCSR<V, I> {
+        let triples: Vec<_> = fxhash.iter().map(|(&(r, c), &v)| (r, c, v)).collect();
+
+        SymmetricCSR::from_triples(&triples, n)
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.upper.n_rows
+    }
+
+    pub fn n_cols(&self) -> usize {
+        self.upper.n_cols
+    }
+
+    /// Number of entries actually stored, i.e. in the upper triangle.
+    pub fn n_stored(&self) -> usize {
+        self.upper.n_nz
+    }
+
+    pub fn upper(&self) -> &CSR<V, I> {
+        &self.upper
+    }
+
+    pub fn value(&self, row: usize, col: usize) -> Option<V> {
+        self.upper.value(row.min(col), row.max(col))
+    }
+
+    pub fn values(&self, idxs: &[(usize, usize)]) -> Vec<Option<V>> {
+        let upper_idxs: Vec<(usize, usize)> = idxs
+            .iter()
+            .map(|&(r, c)| (r.min(c), r.max(c)))
+            .collect();
+        self.upper.values(&upper_idxs)
+    }
+
+    /// Iterates over the full row as `(col, value)` with columns ascending.
+    pub fn inter_row(&self, row: usize) -> SymmetricRowIter<'_, V, I> {
+        self.view().inter_row(row)
+    }
+
+    /// Iterates over the stored upper-triangle entries only.
+    pub fn iter_upper(&self) -> CSRIter<'_, V, I> {
+        self.upper.iter()
+    }
+
+    /// Expands both triangles into a full matrix.
+    pub fn to_csr(&self) -> CSR<V, I> {
+        let mut triples = Vec::with_capacity(2 * self.upper.n_nz);
+        for (row, col, val) in &self.upper {
+            triples.push((row, col, val));
+            if row != col {
+                triples.push((col, row, val));
+            }
+        }
+
+        CSR::from_triples(&triples, self.upper.n_rows, self.upper.n_cols)
+    }
+
+    /// Applies `f` to every stored value, see [`CSR::map_values`].
+    pub fn map_values<W, F>(&self, f: F) -> SymmetricCSR<W, I>
+    where
+        W: Value,
+        F: Fn(V) -> W,
+    {
+        SymmetricCSR::indexed(self.upper.map_values(f))
+    }
+
+    /// Applies `f` to every stored `(row, col, value)` with `row <= col`, see
+    /// [`CSR::map_entries`].
+    pub fn map_entries<W, F>(&self, f: F) -> SymmetricCSR<W, I>
+    where
+        W: Value,
+        F: Fn(usize, usize, V) -> W,
+    {
+        SymmetricCSR::indexed(self.upper.map_entries(f))
+    }
+
+    pub fn size(&self) -> usize {
+        self.upper.size()
+    }
+
+    pub fn add_in_place(&mut self, other: &SymmetricCSR<V, I>) -> Result<()> {
+        self.upper.add_in_place(&other.upper)?;
+        *self = SymmetricCSR::indexed(std::mem::replace(&mut self.upper, CSR::new(0, 0)));
+        Ok(())
+    }
+
+    /// Drops entries smaller than `min`, then keeps an entry if it is among
+    /// the `k` largest remaining entries of either of its two rows, so the
+    /// result stays symmetric (all of them when `k` is `None`). Rows can
+    /// keep more than `k` entries. Ties are broken towards the lower column
+    /// as in [`CSR::prune`].
+    pub fn prune(&self, min: V, k: Option<usize>) -> SymmetricCSR<V, I> {
+        let Some(k) = k else {
+            return SymmetricCSR::indexed(self.upper.prune(min, None));
+        };
+        if k == 0 {
+            return SymmetricCSR::new(self.n_rows());
+        }
+
+        // the k-th largest `(value, col)` of every row holding more than k
+        let by_value_desc = |a: &(V, usize), b: &(V, usize)| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1));
+        let mut kth: Vec<Option<(V, usize)>> = vec![None; self.n_rows()];
+        let mut entries: Vec<(V, usize)> = Vec::new();
+        for (row, kth) in kth.iter_mut().enumerate() {
+            entries.clear();
+            entries.extend(self.inter_row(row).filter(|&(_, v)| v >= min).map(|(col, v)| (v, col)));
+            if k < entries.len() {
+                *kth = Some(*entries.select_nth_unstable_by(k - 1, by_value_desc).1);
+            }
+        }
+        let in_top_k = |row: usize, col: usize, val: V| {
+            kth[row].is_none_or(|kth| by_value_desc(&(val, col), &kth).is_le())
+        };
+
+        let triples: Vec<_> = self
+            .upper
+            .iter()
+            .filter(|&(row, col, val)| val >= min && (in_top_k(row, col, val) || in_top_k(col, row, val)))
+            .collect();
+        SymmetricCSR::indexed(CSR::from_triples(&triples, self.n_rows(), self.n_cols()))
+    }
+}
+
+impl<V: Value, I: Index> Add for &SymmetricCSR<V, I> {
+    type Output = Result<SymmetricCSR<V, I>>;
+
+    fn add(self, other: &SymmetricCSR<V, I>) -> Self::Output {
+        Ok(SymmetricCSR::indexed((&self.upper + &other.upper)?))
+    }
+}
+
+pub struct SymmetricRowIter<'a, V, I> {
+    upper: CSRView<'a, V, I>,
+    index: ColumnIndexView<'a, I>,
+    /// Range of `index.below` still to visit.
+    below: Range<usize>,
+    /// The part of the row right of the diagonal.
+    row: CSRRowIter<'a, V, I>,
+}
+
+impl<V: Value, I: Index> Iterator for SymmetricRowIter<'_, V, I> {
+    type Item = (usize, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // entries left of the diagonal live in column `row` of earlier rows
+        if let Some(i) = self.below.next() {
+            let pos = self.index.below[i].index();
+            let row = self.upper.row_ptr.partition_point(|ptr| ptr.index() <= pos) - 1;
+            return Some((row, self.upper.val[pos]));
+        }
+
+        self.row.next()
+    }
+}
@@ -0,0 +1,386 @@
+//! Borrowed matrices, and an aligned byte layout they can be read from without
+//! copying, e.g. straight out of a memory-mapped file.
+//!
+//! The layout is a 64-byte header followed by `row_ptr`, `col_idx` and `val`,
+//! each starting at a multiple of 64 bytes from the start of the header:
+//!
+//! | offset | field                                       |
+//! |--------|---------------------------------------------|
+//! | 0      | magic `CSRVIEW\0`                           |
+//! | 8      | `n_rows`, `n_cols`, `n_nz` as u64           |
+//! | 32     | index size in bytes as u32                  |
+//! | 36     | value dtype, e.g. `<f4\0`, see [`NpyValue`] |
+//!
+//! A [`SymmetricCSR`] writes its upper triangle in this layout, then its
+//! [`ColumnIndex`] laid out the same way with `col_ptr` and `below` in place
+//! of `row_ptr` and `col_idx`, and no values:
+//!
+//! | offset | field                             |
+//! |--------|-----------------------------------|
+//! | 0      | magic `COLINDEX`                  |
+//! | 8      | `n_cols`, `n_below` as u64        |
+//! | 24     | index size in bytes as u32        |
+//!
+//! All numbers are little-endian.
+
+use crate::iter::CSRRowIter;
+use crate::npz::NpyValue;
+use crate::{ColumnIndex, ColumnIndexView, Error, Index, Result, SymmetricCSR, SymmetricCSRView, Value, CSR};
+use std::io::Write;
+
+const MAGIC: &[u8; 8] = b"CSRVIEW\0";
+const INDEX_MAGIC: &[u8; 8] = b"COLINDEX";
+const ALIGN: usize = 64;
+
+/// A read-only matrix borrowing its arrays, with the lookup API of [`CSR`].
+#[derive(Debug, Clone, Copy)]
+pub struct CSRView<'a, V = f32, I = usize> {
+    pub n_rows: usize,
+    pub n_cols: usize,
+    pub n_nz: usize,
+    pub(crate) row_ptr: &'a [I],
+    pub(crate) col_idx: &'a [I],
+    pub(crate) val: &'a [V],
+}
+
+impl<'a, V: Value, I: Index> CSRView<'a, V, I> {
+    /// See [`CSR::value`].
+    pub fn value(&self, row: usize, col: usize) -> Option<V> {
+        if row >= self.n_rows || col >= self.n_cols {
+            return None;
+        }
+        let row_start = self.row_ptr[row].index();
+        let row_end = self.row_ptr[row + 1].index();
+        match self.col_idx[row_start..row_end].binary_search(&I::from_usize(col)) {
+            Ok(i) => Some(self.val[row_start + i]),
+            Err(_) => Some(V::ZERO),
+        }
+    }
+
+    pub fn inter_row(&self, row: usize) -> CSRRowIter<'a, V, I> {
+        let row_start = self.row_ptr[row].index();
+        let row_end = self.row_ptr[row + 1].index();
+        CSRRowIter::new(&self.col_idx[row_start..row_end], &self.val[row_start..row_end])
+    }
+
+    pub fn to_csr(&self) -> CSR<V, I> {
+        CSR {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            n_nz: self.n_nz,
+            row_ptr: self.row_ptr.to_vec(),
+            col_idx: self.col_idx.to_vec(),
+            val: self.val.to_vec(),
+        }
+    }
+
+    /// Checks in O(n_rows + n_nz) that the arrays agree with the shape and
+    /// with each other: row pointers run from zero to `n_nz` without
+    /// decreasing, and every row holds strictly increasing columns below
+    /// `n_cols`, so lookups never panic. Values are not checked.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.row_ptr.len() != self.n_rows.saturating_add(1)
+            || self.col_idx.len() != self.n_nz
+            || self.val.len() != self.n_nz
+        {
+            return Err("CSR arrays do not match the matrix shape".into());
+        }
+        let inconsistent = || "Inconsistent row pointers in CSR matrix".into();
+        if self.row_ptr[0].index() != 0 || self.row_ptr[self.n_rows].index() != self.n_nz {
+            return Err(inconsistent());
+        }
+        for row in 0..self.n_rows {
+            let (row_start, row_end) = (self.row_ptr[row].index(), self.row_ptr[row + 1].index());
+            if row_start > row_end || row_end > self.n_nz {
+                return Err(inconsistent());
+            }
+            let cols = &self.col_idx[row_start..row_end];
+            if cols.windows(2).any(|w| w[0] >= w[1]) || cols.last().is_some_and(|c| c.index() >= self.n_cols) {
+                return Err(format!("Row {row} of CSR matrix has unsorted or out of bounds columns").into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, V: NpyValue, I: Index> CSRView<'a, V, I> {
+    /// Views a matrix written by [`CSR::write_aligned`]. `bytes` must start
+    /// at an address aligned for `V` and `I`, which a memory map or any
+    /// 64-byte aligned offset into one is.
+    ///
+    /// Besides the header and array bounds, checks the arrays in
+    /// O(n_rows + n_nz) as [`CSR::try_from_parts`] does, so lookups on the
+    /// view never panic.
+    pub fn from_aligned(bytes: &'a [u8]) -> Result<CSRView<'a, V, I>> {
+        let view: CSRView<V, I> = CSRView::from_aligned_trusted(bytes)?;
+        view.check()?;
+
+        Ok(view)
+    }
+
+    /// [`CSRView::from_aligned`] checking only the header and array bounds,
+    /// in O(1). Meant for bytes that have already been checked, such as a
+    /// memory map viewed again; on other bytes, lookups may give wrong
+    /// results or panic, but never cause undefined behaviour.
+    pub fn from_aligned_trusted(bytes: &'a [u8]) -> Result<CSRView<'a, V, I>> {
+        if cfg!(target_endian = "big") {
+            return Err("Aligned matrices can only be viewed on little-endian targets".into());
+        }
+        if bytes.len() < ALIGN || &bytes[..8] != MAGIC {
+            return Err("Not an aligned CSR matrix".into());
+        }
+
+        let too_large = || "Aligned CSR matrix is too large for this target".into();
+        let header_u64 = |at: usize| -> Result<usize> {
+            let n = u64::from_le_bytes(bytes[at..at + 8].try_into().expect("slice is 8 bytes"));
+            usize::try_from(n).map_err(|_| too_large())
+        };
+        let n_rows = header_u64(8)?;
+        let n_cols = header_u64(16)?;
+        let n_nz = header_u64(24)?;
+
+        let index_size = u32::from_le_bytes(bytes[32..36].try_into().expect("slice is 4 bytes")) as usize;
+        if index_size != size_of::<I>() {
+            return Err(format!(
+                "Cannot view {index_size}-byte indices as {}-byte indices",
+                size_of::<I>()
+            )
+            .into());
+        }
+        let descr = String::from_utf8_lossy(&bytes[36..40]);
+        let descr = descr.trim_end_matches('\0');
+        if descr != V::DESCR {
+            return Err(format!("Cannot view values of dtype '{descr}' as '{}'", V::DESCR).into());
+        }
+
+        let Some(layout) = Layout::new::<V, I>(n_rows, n_nz) else {
+            return Err(too_large());
+        };
+        if bytes.len() < layout.end {
+            return Err("Truncated aligned CSR matrix".into());
+        }
+        let cast_err = |e| format!("Cannot view aligned CSR arrays: {e}");
+        // the layout fits in `bytes`, so none of these ranges overflow
+        let row_ptr: &[I] =
+            bytemuck::try_cast_slice(&bytes[layout.row_ptr_at..layout.row_ptr_at + (n_rows + 1) * size_of::<I>()])
+                .map_err(cast_err)?;
+        let col_idx: &[I] =
+            bytemuck::try_cast_slice(&bytes[layout.col_idx_at..layout.col_idx_at + n_nz * size_of::<I>()])
+                .map_err(cast_err)?;
+        let val: &[V] = bytemuck::try_cast_slice(&bytes[layout.val_at..layout.val_at + n_nz * size_of::<V>()])
+            .map_err(cast_err)?;
+
+        Ok(CSRView {
+            n_rows,
+            n_cols,
+            n_nz,
+            row_ptr,
+            col_idx,
+            val,
+        })
+    }
+}
+
+impl<V: Value, I: Index> CSR<V, I> {
+    pub fn view(&self) -> CSRView<'_, V, I> {
+        CSRView {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            n_nz: self.n_nz,
+            row_ptr: &self.row_ptr,
+            col_idx: &self.col_idx,
+            val: &self.val,
+        }
+    }
+}
+
+impl<V: NpyValue, I: Index> CSR<V, I> {
+    /// Writes the layout read by [`CSRView::from_aligned`]. Offsets are
+    /// relative to where the writer starts, so an embedding file should
+    /// start the matrix at a multiple of 64 bytes.
+    pub fn write_aligned<W: Write>(&self, mut writer: W) -> Result<()> {
+        if cfg!(target_endian = "big") {
+            return Err("Aligned matrices can only be written on little-endian targets".into());
+        }
+
+        let mut header = [0u8; ALIGN];
+        header[..8].copy_from_slice(MAGIC);
+        header[8..16].copy_from_slice(&(self.n_rows as u64).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.n_cols as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&(self.n_nz as u64).to_le_bytes());
+        header[32..36].copy_from_slice(&(size_of::<I>() as u32).to_le_bytes());
+        header[36..36 + V::DESCR.len()].copy_from_slice(V::DESCR.as_bytes());
+        writer.write_all(&header)?;
+
+        // the arrays are in memory, so their padded sizes fit in a usize
+        let layout = Layout::new::<V, I>(self.n_rows, self.n_nz).expect("matrix fits in memory");
+        write_padded(
+            &mut writer,
+            [
+                (bytemuck::cast_slice(&self.row_ptr), layout.col_idx_at),
+                (bytemuck::cast_slice(&self.col_idx), layout.val_at),
+                (bytemuck::cast_slice(&self.val), layout.end),
+            ],
+        )?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<I: Index> ColumnIndex<I> {
+    /// Writes the index layout read by [`ColumnIndexView::from_aligned_trusted`].
+    fn write_aligned<W: Write>(&self, mut writer: W) -> Result<()> {
+        let n_cols = self.col_ptr.len() - 1;
+        let mut header = [0u8; ALIGN];
+        header[..8].copy_from_slice(INDEX_MAGIC);
+        header[8..16].copy_from_slice(&(n_cols as u64).to_le_bytes());
+        header[16..24].copy_from_slice(&(self.below.len() as u64).to_le_bytes());
+        header[24..28].copy_from_slice(&(size_of::<I>() as u32).to_le_bytes());
+        writer.write_all(&header)?;
+
+        let layout = Layout::new::<(), I>(n_cols, self.below.len()).expect("index fits in memory");
+        write_padded(
+            &mut writer,
+            [
+                (bytemuck::cast_slice(&self.col_ptr), layout.col_idx_at),
+                (bytemuck::cast_slice(&self.below), layout.end),
+            ],
+        )?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<'a, I: Index> ColumnIndexView<'a, I> {
+    /// Views an index written after an upper triangle by
+    /// [`SymmetricCSR::write_aligned`], checking only the header and array
+    /// bounds, in O(1).
+    fn from_aligned_trusted(bytes: &'a [u8]) -> Result<ColumnIndexView<'a, I>> {
+        if bytes.len() < ALIGN || &bytes[..8] != INDEX_MAGIC {
+            return Err("Not an aligned column index".into());
+        }
+
+        let too_large = || "Aligned column index is too large for this target".into();
+        let header_u64 = |at: usize| -> Result<usize> {
+            let n = u64::from_le_bytes(bytes[at..at + 8].try_into().expect("slice is 8 bytes"));
+            usize::try_from(n).map_err(|_| too_large())
+        };
+        let n_cols = header_u64(8)?;
+        let n_below = header_u64(16)?;
+        let index_size = u32::from_le_bytes(bytes[24..28].try_into().expect("slice is 4 bytes")) as usize;
+        if index_size != size_of::<I>() {
+            return Err(format!(
+                "Cannot view {index_size}-byte indices as {}-byte indices",
+                size_of::<I>()
+            )
+            .into());
+        }
+
+        let Some(layout) = Layout::new::<(), I>(n_cols, n_below) else {
+            return Err(too_large());
+        };
+        if bytes.len() < layout.end {
+            return Err("Truncated aligned column index".into());
+        }
+        let cast_err = |e| format!("Cannot view aligned column index: {e}");
+        let col_ptr: &[I] =
+            bytemuck::try_cast_slice(&bytes[layout.row_ptr_at..layout.row_ptr_at + (n_cols + 1) * size_of::<I>()])
+                .map_err(cast_err)?;
+        let below: &[I] = bytemuck::try_cast_slice(&bytes[layout.col_idx_at..layout.col_idx_at + n_below * size_of::<I>()])
+            .map_err(cast_err)?;
+
+        Ok(ColumnIndexView { col_ptr, below })
+    }
+}
+
+impl<'a, V: NpyValue, I: Index> SymmetricCSRView<'a, V, I> {
+    /// Views a matrix written by [`SymmetricCSR::write_aligned`], with the
+    /// alignment [`CSRView::from_aligned`] needs.
+    ///
+    /// Checks the upper triangle as [`CSRView::from_aligned`] does, and the
+    /// stored index against one built from it, in O(n_rows + n_nz) and a
+    /// copy of the index, so lookups on the view never panic.
+    pub fn from_aligned(bytes: &'a [u8]) -> Result<SymmetricCSRView<'a, V, I>> {
+        let view: SymmetricCSRView<V, I> = SymmetricCSRView::from_aligned_trusted(bytes)?;
+        let upper: CSRView<V, I> = CSRView::from_aligned(bytes)?;
+        if ColumnIndex::new(upper)?.view() != view.index() {
+            return Err("Column index does not match its aligned upper triangle".into());
+        }
+
+        Ok(view)
+    }
+
+    /// [`SymmetricCSRView::from_aligned`] checking only the headers, array
+    /// bounds and that the matrix is square, in O(1), see
+    /// [`CSRView::from_aligned_trusted`].
+    pub fn from_aligned_trusted(bytes: &'a [u8]) -> Result<SymmetricCSRView<'a, V, I>> {
+        let upper: CSRView<V, I> = CSRView::from_aligned_trusted(bytes)?;
+        if upper.n_rows != upper.n_cols {
+            return Err(Error::NotSquare {
+                n_rows: upper.n_rows,
+                n_cols: upper.n_cols,
+            });
+        }
+        // the upper triangle fits in `bytes`, so its layout fits in a usize
+        let index_at = Layout::new::<V, I>(upper.n_rows, upper.n_nz).expect("matrix fits in bytes").end;
+        let index: ColumnIndexView<I> = ColumnIndexView::from_aligned_trusted(bytes.get(index_at..).unwrap_or_default())?;
+        if index.col_ptr.len() != upper.n_cols + 1 {
+            return Err("Column index does not match its aligned upper triangle".into());
+        }
+
+        Ok(SymmetricCSRView::new(upper, index))
+    }
+}
+
+impl<V: NpyValue, I: Index> SymmetricCSR<V, I> {
+    /// Writes the upper triangle in the layout read by
+    /// [`CSRView::from_aligned`], then its column index, for
+    /// [`SymmetricCSRView::from_aligned`].
+    pub fn write_aligned<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.upper().write_aligned(&mut writer)?;
+        self.index.write_aligned(writer)
+    }
+}
+
+/// Writes each array zero-padded up to the offset paired with it, starting
+/// right after a header.
+fn write_padded<W: Write, const N: usize>(writer: &mut W, arrays: [(&[u8], usize); N]) -> Result<()> {
+    let mut written = ALIGN;
+    for (bytes, next) in arrays {
+        writer.write_all(bytes)?;
+        writer.write_all(&vec![0; next - written - bytes.len()])?;
+        written = next;
+    }
+
+    Ok(())
+}
+
+/// Offsets of `row_ptr`, `col_idx` and `val`, and the padded end of the
+/// layout. A column index is laid out with `V = ()`, which has no values.
+struct Layout {
+    row_ptr_at: usize,
+    col_idx_at: usize,
+    val_at: usize,
+    end: usize,
+}
+
+impl Layout {
+    /// `None` if the layout does not fit in a `usize`.
+    fn new<V, I>(n_rows: usize, n_nz: usize) -> Option<Layout> {
+        let padded = |n: usize, size: usize| n.checked_mul(size)?.checked_next_multiple_of(ALIGN);
+
+        let row_ptr_at = ALIGN;
+        let col_idx_at = row_ptr_at.checked_add(padded(n_rows.checked_add(1)?, size_of::<I>())?)?;
+        let val_at = col_idx_at.checked_add(padded(n_nz, size_of::<I>())?)?;
+        let end = val_at.checked_add(padded(n_nz, size_of::<V>())?)?;
+        Some(Layout {
+            row_ptr_at,
+            col_idx_at,
+            val_at,
+            end,
+        })
+    }
+}
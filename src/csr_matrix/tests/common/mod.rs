@@ -0,0 +1,137 @@
+//! A naive dense matrix to check CSR operations against, and strategies for
+//! random small matrices. Values are small integers stored as `f64`, so sums
+//! and products are exact and results can be compared with `==`.
+
+// each test file uses a different subset
+#![allow(dead_code)]
+
+use csr_matrix::CSR;
+use proptest::prelude::*;
+
+pub type M = CSR<f64, u32>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dense {
+    pub n_rows: usize,
+    pub n_cols: usize,
+    pub data: Vec<f64>,
+}
+
+impl Dense {
+    pub fn zeros(n_rows: usize, n_cols: usize) -> Dense {
+        Dense {
+            n_rows,
+            n_cols,
+            data: vec![0.0; n_rows * n_cols],
+        }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row * self.n_cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, val: f64) {
+        self.data[row * self.n_cols + col] = val;
+    }
+
+    pub fn triples(&self) -> Vec<(usize, usize, f64)> {
+        let mut triples = Vec::new();
+        for row in 0..self.n_rows {
+            for col in 0..self.n_cols {
+                if self.get(row, col) != 0.0 {
+                    triples.push((row, col, self.get(row, col)));
+                }
+            }
+        }
+        triples
+    }
+
+    pub fn to_csr(&self) -> M {
+        CSR::from_triples(&self.triples(), self.n_rows, self.n_cols)
+    }
+
+    pub fn from_csr(csr: &M) -> Dense {
+        let mut dense = Dense::zeros(csr.n_rows, csr.n_cols);
+        for (row, col, val) in csr {
+            dense.set(row, col, val);
+        }
+        dense
+    }
+
+    pub fn zip(&self, other: &Dense, f: impl Fn(f64, f64) -> f64) -> Dense {
+        Dense {
+            n_rows: self.n_rows,
+            n_cols: self.n_cols,
+            data: self.data.iter().zip(&other.data).map(|(&a, &b)| f(a, b)).collect(),
+        }
+    }
+
+    pub fn matmul(&self, other: &Dense) -> Dense {
+        let mut out = Dense::zeros(self.n_rows, other.n_cols);
+        for i in 0..self.n_rows {
+            for j in 0..other.n_cols {
+                let sum = (0..self.n_cols).map(|k| self.get(i, k) * other.get(k, j)).sum();
+                out.set(i, j, sum);
+            }
+        }
+        out
+    }
+
+    pub fn transpose(&self) -> Dense {
+        let mut out = Dense::zeros(self.n_cols, self.n_rows);
+        for row in 0..self.n_rows {
+            for col in 0..self.n_cols {
+                out.set(col, row, self.get(row, col));
+            }
+        }
+        out
+    }
+}
+
+/// Rows hold sorted, unique columns and no explicit zeros.
+pub fn assert_canonical(csr: &M) {
+    let entries: Vec<_> = csr.iter().collect();
+    assert_eq!(entries.len(), csr.n_nz);
+    for w in entries.windows(2) {
+        assert!((w[0].0, w[0].1) < (w[1].0, w[1].1), "unsorted entries {w:?}");
+    }
+    for &(row, col, val) in &entries {
+        assert!(row < csr.n_rows && col < csr.n_cols);
+        assert_ne!(val, 0.0, "explicit zero at ({row}, {col})");
+    }
+}
+
+pub fn cell() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        3 => Just(0.0),
+        2 => (-3i32..=3).prop_map(f64::from),
+    ]
+}
+
+pub fn dense(n_rows: usize, n_cols: usize) -> impl Strategy<Value = Dense> {
+    prop::collection::vec(cell(), n_rows * n_cols).prop_map(move |data| Dense {
+        n_rows,
+        n_cols,
+        data,
+    })
+}
+
+pub fn any_dense() -> impl Strategy<Value = Dense> {
+    (0..7usize, 0..7usize).prop_flat_map(|(r, c)| dense(r, c))
+}
+
+pub fn symmetric_dense() -> impl Strategy<Value = Dense> {
+    (0..7usize).prop_flat_map(symmetric)
+}
+
+pub fn symmetric(n: usize) -> impl Strategy<Value = Dense> {
+    dense(n, n).prop_map(|d| {
+        let mut sym = d.clone();
+        for row in 0..d.n_rows {
+            for col in 0..row {
+                sym.set(row, col, d.get(col, row));
+            }
+        }
+        sym
+    })
+}
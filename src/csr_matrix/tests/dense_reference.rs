@@ -0,0 +1,680 @@
+//! Checks every CSR operation against a naive dense implementation on random
+//! small matrices, see [`common::Dense`].
+
+mod common;
+
+use common::*;
+use csr_matrix::{CSRBuilder, CSRView, ColumnIndex, Error, SymmetricCSR, SymmetricCSRView, CSC, CSR};
+use proptest::prelude::*;
+use rustc_hash::FxHashMap;
+
+fn same_size_pair() -> impl Strategy<Value = (Dense, Dense)> {
+    (0..7usize, 0..7usize).prop_flat_map(|(r, c)| (dense(r, c), dense(r, c)))
+}
+
+fn product_pair() -> impl Strategy<Value = (Dense, Dense)> {
+    (0..6usize, 0..6usize, 0..6usize).prop_flat_map(|(r, k, c)| (dense(r, k), dense(k, c)))
+}
+
+fn square_dense() -> impl Strategy<Value = Dense> {
+    (0..7usize).prop_flat_map(|n| dense(n, n))
+}
+
+fn symmetric_pair() -> impl Strategy<Value = (Dense, Dense)> {
+    (0..7usize).prop_flat_map(|n| (symmetric(n), symmetric(n)))
+}
+
+#[derive(Debug, Clone)]
+enum Write {
+    Insert(usize, usize, f64),
+    Remove(usize, usize),
+}
+
+fn writes(n_rows: usize, n_cols: usize) -> impl Strategy<Value = Vec<Write>> {
+    let write = prop_oneof![
+        (0..n_rows, 0..n_cols, cell()).prop_map(|(r, c, v)| Write::Insert(r, c, v)),
+        (0..n_rows, 0..n_cols).prop_map(|(r, c)| Write::Remove(r, c)),
+    ];
+    prop::collection::vec(write, 0..30)
+}
+
+fn matrix_and_writes() -> impl Strategy<Value = (Dense, Vec<Write>)> {
+    (1..7usize, 1..7usize).prop_flat_map(|(r, c)| (dense(r, c), writes(r, c)))
+}
+
+fn triples_with_duplicates() -> impl Strategy<Value = (usize, usize, Vec<(usize, usize, f64)>)> {
+    (1..7usize, 1..7usize).prop_flat_map(|(r, c)| {
+        let triple = (0..r, 0..c, cell());
+        (Just(r), Just(c), prop::collection::vec(triple, 0..40))
+    })
+}
+
+fn matrix_and_selection() -> impl Strategy<Value = (Dense, Vec<usize>, Vec<usize>)> {
+    (1..7usize, 1..7usize).prop_flat_map(|(r, c)| {
+        let rows = prop::collection::vec(0..r, 0..8);
+        let cols = Just((0..c).collect::<Vec<_>>()).prop_shuffle();
+        (dense(r, c), rows, cols, 0..=c)
+            .prop_map(|(d, rows, mut cols, n)| {
+                cols.truncate(n);
+                (d, rows, cols)
+            })
+    })
+}
+
+fn many_same_size() -> impl Strategy<Value = Vec<Dense>> {
+    (0..6usize, 0..6usize, 1..5usize)
+        .prop_flat_map(|(r, c, k)| prop::collection::vec(dense(r, c), k))
+}
+
+fn many_any_size() -> impl Strategy<Value = Vec<Dense>> {
+    prop::collection::vec(any_dense(), 0..5)
+}
+
+fn matrix_and_cell() -> impl Strategy<Value = (Dense, usize, usize)> {
+    (1..7usize, 1..7usize).prop_flat_map(|(r, c)| (dense(r, c), 0..r, 0..c))
+}
+
+/// The shapes of a dimension mismatch error, `None` for any other result.
+fn mismatch<T>(result: csr_matrix::Result<T>) -> Option<((usize, usize), (usize, usize))> {
+    match result {
+        Err(Error::DimensionMismatch { lhs, rhs }) => Some((lhs, rhs)),
+        _ => None,
+    }
+}
+
+proptest! {
+    #[test]
+    fn from_triples_round_trips(d in any_dense()) {
+        let csr = d.to_csr();
+        assert_canonical(&csr);
+        prop_assert_eq!(Dense::from_csr(&csr), d);
+    }
+
+    #[test]
+    fn value_and_get_row_match_dense(d in any_dense()) {
+        let csr = d.to_csr();
+        for row in 0..d.n_rows {
+            for col in 0..d.n_cols {
+                prop_assert_eq!(csr.value(row, col), Some(d.get(row, col)));
+            }
+            let dense_row: Vec<f64> = (0..d.n_cols).map(|col| d.get(row, col)).collect();
+            prop_assert_eq!(csr.get_row(row), Some(dense_row));
+        }
+        prop_assert_eq!(csr.value(d.n_rows, 0), None);
+        prop_assert_eq!(csr.value(0, d.n_cols), None);
+        prop_assert_eq!(csr.get_row(d.n_rows), None);
+    }
+
+    #[test]
+    fn values_matches_value(d in any_dense(), idxs in prop::collection::vec((0..8usize, 0..8usize), 0..20)) {
+        let csr = d.to_csr();
+        let expected: Vec<_> = idxs.iter().map(|&(r, c)| csr.value(r, c)).collect();
+        prop_assert_eq!(csr.values(&idxs), expected);
+    }
+
+    #[test]
+    fn add_matches_dense((a, b) in same_size_pair()) {
+        let sum = (&a.to_csr() + &b.to_csr()).unwrap();
+        assert_canonical(&sum);
+        prop_assert_eq!(Dense::from_csr(&sum), a.zip(&b, |x, y| x + y));
+    }
+
+    #[test]
+    fn add_of_negation_is_empty(a in any_dense()) {
+        let csr = a.to_csr();
+        let sum = (&csr + &(&csr * -1.0)).unwrap();
+        prop_assert_eq!(sum.n_nz, 0);
+    }
+
+    #[test]
+    fn add_in_place_matches_add((a, b) in same_size_pair()) {
+        let mut lhs = a.to_csr();
+        lhs.add_in_place(&b.to_csr()).unwrap();
+        assert_canonical(&lhs);
+        prop_assert_eq!(Dense::from_csr(&lhs), a.zip(&b, |x, y| x + y));
+    }
+
+    #[test]
+    fn sub_matches_dense((a, b) in same_size_pair()) {
+        let diff = (&a.to_csr() - &b.to_csr()).unwrap();
+        assert_canonical(&diff);
+        prop_assert_eq!(Dense::from_csr(&diff), a.zip(&b, |x, y| x - y));
+    }
+
+    #[test]
+    fn hadamard_matches_dense((a, b) in same_size_pair()) {
+        let prod = a.to_csr().hadamard(&b.to_csr()).unwrap();
+        assert_canonical(&prod);
+        prop_assert_eq!(Dense::from_csr(&prod), a.zip(&b, |x, y| x * y));
+    }
+
+    #[test]
+    fn div_elementwise_matches_dense((a, b) in same_size_pair()) {
+        let quot = a.to_csr().div_elementwise(&b.to_csr()).unwrap();
+        assert_canonical(&quot);
+        let expected = a.zip(&b, |x, y| if y == 0.0 { 0.0 } else { x / y });
+        prop_assert_eq!(Dense::from_csr(&quot), expected);
+    }
+
+    #[test]
+    fn zip_map_matches_dense((a, b) in same_size_pair()) {
+        let f = |x: f64, y: f64| 2.0 * x - y;
+        let zipped = a.to_csr().zip_map(&b.to_csr(), f).unwrap();
+        assert_canonical(&zipped);
+        prop_assert_eq!(Dense::from_csr(&zipped), a.zip(&b, f));
+    }
+
+    #[test]
+    fn scalar_ops_match_dense(a in any_dense(), s in -3i32..=3) {
+        let s = f64::from(s);
+        let csr = a.to_csr();
+        let scaled = &csr * s;
+        assert_canonical(&scaled);
+        prop_assert_eq!(Dense::from_csr(&scaled), a.zip(&a, |x, _| x * s));
+        if s != 0.0 {
+            let divided = &csr / s;
+            prop_assert_eq!(Dense::from_csr(&divided), a.zip(&a, |x, _| x / s));
+        }
+    }
+
+    #[test]
+    fn map_values_drops_zeros(a in any_dense()) {
+        let mapped = a.to_csr().map_values(|v| if v > 0.0 { v } else { 0.0 });
+        assert_canonical(&mapped);
+        prop_assert_eq!(Dense::from_csr(&mapped), a.zip(&a, |x, _| x.max(0.0)));
+    }
+
+    #[test]
+    fn mismatched_sizes_are_errors(a in any_dense()) {
+        let csr = a.to_csr();
+        let other = M::new(a.n_rows + 1, a.n_cols);
+        let shapes = Some(((a.n_rows, a.n_cols), (a.n_rows + 1, a.n_cols)));
+        prop_assert_eq!(mismatch(&csr + &other), shapes);
+        prop_assert_eq!(mismatch(&csr - &other), shapes);
+        prop_assert_eq!(mismatch(csr.hadamard(&other)), shapes);
+        prop_assert_eq!(
+            mismatch(&csr * &M::new(a.n_cols + 1, 2)),
+            Some(((a.n_rows, a.n_cols), (a.n_cols + 1, 2)))
+        );
+        prop_assert_eq!(
+            mismatch(csr.mul_vec(&vec![0.0; a.n_cols + 1])),
+            Some(((a.n_rows, a.n_cols), (a.n_cols + 1, 1)))
+        );
+        prop_assert!(matches!(CSR::<f64, u32>::merge_add(&[]), Err(Error::NoMatrices)));
+    }
+
+    #[test]
+    fn mul_matches_dense((a, b) in product_pair()) {
+        let prod = (&a.to_csr() * &b.to_csr()).unwrap();
+        assert_canonical(&prod);
+        prop_assert_eq!(Dense::from_csr(&prod), a.matmul(&b));
+    }
+
+    #[test]
+    fn mul_vec_matches_dense(a in any_dense(), seed in prop::collection::vec(-3i32..=3, 7)) {
+        let csr = a.to_csr();
+        let x: Vec<f64> = seed.iter().take(a.n_cols).map(|&v| f64::from(v)).collect();
+        let expected: Vec<f64> = (0..a.n_rows)
+            .map(|i| (0..a.n_cols).map(|j| a.get(i, j) * x[j]).sum())
+            .collect();
+        prop_assert_eq!(csr.mul_vec(&x).unwrap(), expected.clone());
+        prop_assert_eq!((&csr * x.as_slice()).unwrap(), expected);
+
+        let y: Vec<f64> = seed.iter().take(a.n_rows).map(|&v| f64::from(v)).collect();
+        let expected_t: Vec<f64> = (0..a.n_cols)
+            .map(|j| (0..a.n_rows).map(|i| a.get(i, j) * y[i]).sum())
+            .collect();
+        prop_assert_eq!(csr.transpose_mul_vec(&y).unwrap(), expected_t);
+    }
+
+    #[test]
+    fn transpose_matches_dense(a in any_dense()) {
+        let t = a.to_csr().transpose();
+        assert_canonical(&t);
+        prop_assert_eq!(Dense::from_csr(&t), a.transpose());
+    }
+
+    #[test]
+    fn csc_round_trips(a in any_dense()) {
+        let csr = a.to_csr();
+        let csc: CSC<f64, u32> = csr.to_csc();
+        for row in 0..a.n_rows {
+            for col in 0..a.n_cols {
+                prop_assert_eq!(csc.value(row, col), Some(a.get(row, col)));
+            }
+        }
+        for col in 0..a.n_cols {
+            let expected: Vec<(usize, f64)> = (0..a.n_rows)
+                .filter(|&row| a.get(row, col) != 0.0)
+                .map(|row| (row, a.get(row, col)))
+                .collect();
+            prop_assert_eq!(csc.inter_col(col).collect::<Vec<_>>(), expected);
+        }
+        prop_assert_eq!(Dense::from_csr(&csc.to_csr()), a);
+    }
+
+    #[test]
+    fn writes_match_dense((a, ops) in matrix_and_writes()) {
+        let mut csr = a.to_csr();
+        let mut dense = a.clone();
+        for op in &ops {
+            match *op {
+                Write::Insert(r, c, v) => {
+                    let prev = dense.get(r, c);
+                    let expected = if prev == 0.0 { None } else { Some(prev) };
+                    prop_assert_eq!(csr.insert(r, c, v).unwrap(), expected);
+                    dense.set(r, c, v);
+                }
+                Write::Remove(r, c) => {
+                    let prev = dense.get(r, c);
+                    let expected = if prev == 0.0 { None } else { Some(prev) };
+                    prop_assert_eq!(csr.remove(r, c).unwrap(), expected);
+                    dense.set(r, c, 0.0);
+                }
+            }
+        }
+        assert_canonical(&csr);
+        prop_assert_eq!(Dense::from_csr(&csr), dense);
+    }
+
+    #[test]
+    fn batch_matches_dense((a, ops) in matrix_and_writes()) {
+        let mut csr = a.to_csr();
+        let mut dense = a.clone();
+        let mut batch = csr.batch();
+        for op in &ops {
+            match *op {
+                Write::Insert(r, c, v) => {
+                    batch.insert(r, c, v);
+                    dense.set(r, c, v);
+                }
+                Write::Remove(r, c) => {
+                    batch.remove(r, c);
+                    dense.set(r, c, 0.0);
+                }
+            }
+        }
+        batch.commit().unwrap();
+        assert_canonical(&csr);
+        prop_assert_eq!(Dense::from_csr(&csr), dense);
+    }
+
+    #[test]
+    fn update_matches_dense((a, r, c) in matrix_and_cell()) {
+        let mut csr = a.to_csr();
+        let new = csr.update(r, c, |v| v + 1.0).unwrap();
+        prop_assert_eq!(new, a.get(r, c) + 1.0);
+        prop_assert_eq!(csr.value(r, c), Some(new));
+        assert_canonical(&csr);
+    }
+
+    #[test]
+    fn symmetric_matches_dense(d in symmetric_dense()) {
+        let full = d.to_csr();
+        let sym: SymmetricCSR<f64, u32> = SymmetricCSR::from_csr(&full).unwrap();
+        prop_assert_eq!(Dense::from_csr(&sym.to_csr()), d.clone());
+        for row in 0..d.n_rows {
+            prop_assert_eq!(
+                sym.inter_row(row).collect::<Vec<_>>(),
+                full.inter_row(row).collect::<Vec<_>>()
+            );
+            for col in 0..d.n_cols {
+                prop_assert_eq!(sym.value(row, col), Some(d.get(row, col)));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_rows_follow_updates((d, e) in symmetric_pair()) {
+        let mut sym: SymmetricCSR<f64, u32> = SymmetricCSR::from_csr(&d.to_csr()).unwrap();
+        sym.add_in_place(&SymmetricCSR::from_csr(&e.to_csr()).unwrap()).unwrap();
+        let sym = sym.map_values(|v| v * 2.0);
+
+        let full = d.zip(&e, |x, y| 2.0 * (x + y)).to_csr();
+        for row in 0..d.n_rows {
+            prop_assert_eq!(
+                sym.inter_row(row).collect::<Vec<_>>(),
+                full.inter_row(row).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn prune_threshold_matches_dense(a in any_dense(), min in -3i32..=3) {
+        let min = f64::from(min);
+        let pruned = a.to_csr().prune_threshold(min);
+        assert_canonical(&pruned);
+        prop_assert_eq!(Dense::from_csr(&pruned), a.zip(&a, |x, _| if x >= min { x } else { 0.0 }));
+    }
+
+    #[test]
+    fn top_k_per_row_matches_dense(a in any_dense(), k in 0..8usize, min in -3i32..=3) {
+        let min = f64::from(min);
+        let pruned = a.to_csr().prune(min, Some(k));
+        assert_canonical(&pruned);
+
+        let mut expected = Dense::zeros(a.n_rows, a.n_cols);
+        for row in 0..a.n_rows {
+            let mut cells: Vec<(usize, f64)> = (0..a.n_cols)
+                .map(|col| (col, a.get(row, col)))
+                .filter(|&(_, v)| v != 0.0 && v >= min)
+                .collect();
+            cells.sort_by(|x, y| y.1.total_cmp(&x.1).then(x.0.cmp(&y.0)));
+            for &(col, v) in cells.iter().take(k) {
+                expected.set(row, col, v);
+            }
+        }
+        prop_assert_eq!(Dense::from_csr(&pruned), expected);
+    }
+
+    #[test]
+    fn builder_sums_duplicates((n_rows, n_cols, triples) in triples_with_duplicates(), limit in 1..10usize) {
+        let mut expected = Dense::zeros(n_rows, n_cols);
+        for &(r, c, v) in &triples {
+            expected.set(r, c, expected.get(r, c) + v);
+        }
+
+        let mut builder: CSRBuilder<f64, u32> = CSRBuilder::new(n_rows, n_cols).max_buffered(limit);
+        builder.extend(triples.iter().copied());
+        let built = builder.build();
+        assert_canonical(&built);
+        prop_assert_eq!(Dense::from_csr(&built), expected.clone());
+
+        let from_triples: M = CSR::from_triples(&triples, n_rows, n_cols);
+        assert_canonical(&from_triples);
+        prop_assert_eq!(Dense::from_csr(&from_triples), expected);
+    }
+
+    #[test]
+    fn submatrix_matches_dense((a, rows, cols) in matrix_and_selection()) {
+        let csr = a.to_csr();
+        let mut expected = Dense::zeros(rows.len(), cols.len());
+        for (i, &r) in rows.iter().enumerate() {
+            for (j, &c) in cols.iter().enumerate() {
+                expected.set(i, j, a.get(r, c));
+            }
+        }
+
+        let sub = csr.submatrix(&rows, &cols);
+        assert_canonical(&sub);
+        prop_assert_eq!(Dense::from_csr(&sub), expected);
+
+        let by_rows_then_cols = csr.select_rows(&rows).select_cols(&cols);
+        assert_canonical(&by_rows_then_cols);
+        prop_assert_eq!(Dense::from_csr(&by_rows_then_cols), Dense::from_csr(&sub));
+    }
+
+    #[test]
+    fn compact_drops_only_empty_lines(a in any_dense()) {
+        let (compacted, row_map, col_map) = a.to_csr().compact();
+        assert_canonical(&compacted);
+        prop_assert_eq!(compacted.n_nz, a.triples().len());
+        for (r, c, v) in a.triples() {
+            let (nr, nc) = (row_map[r].unwrap(), col_map[c].unwrap());
+            prop_assert_eq!(compacted.value(nr, nc), Some(v));
+        }
+        for nr in 0..compacted.n_rows {
+            prop_assert!(compacted.inter_row(nr).next().is_some());
+        }
+        prop_assert_eq!(compacted.n_rows, row_map.iter().flatten().count());
+        prop_assert_eq!(compacted.n_cols, col_map.iter().flatten().count());
+    }
+
+    #[test]
+    fn compact_square_keeps_lines_used_on_either_axis(a in square_dense()) {
+        let (compacted, map) = a.to_csr().compact_square();
+        assert_canonical(&compacted);
+        prop_assert_eq!(map.len(), a.n_rows);
+        for (i, new) in map.iter().enumerate() {
+            let used = (0..a.n_rows).any(|j| a.get(i, j) != 0.0 || a.get(j, i) != 0.0);
+            prop_assert_eq!(new.is_some(), used);
+        }
+        // kept indices keep their order
+        let kept: Vec<usize> = map.iter().flatten().copied().collect();
+        prop_assert_eq!(&kept, &(0..kept.len()).collect::<Vec<_>>());
+
+        prop_assert_eq!((compacted.n_rows, compacted.n_cols), (kept.len(), kept.len()));
+        prop_assert_eq!(compacted.n_nz, a.triples().len());
+        for (r, c, v) in a.triples() {
+            prop_assert_eq!(compacted.value(map[r].unwrap(), map[c].unwrap()), Some(v));
+        }
+    }
+
+    #[test]
+    fn merge_add_matches_dense(ds in many_same_size()) {
+        let csrs: Vec<M> = ds.iter().map(Dense::to_csr).collect();
+        let sum = CSR::merge_add(&csrs.iter().collect::<Vec<_>>()).unwrap();
+        assert_canonical(&sum);
+        let expected = ds[1..].iter().fold(ds[0].clone(), |acc, d| acc.zip(d, |x, y| x + y));
+        prop_assert_eq!(Dense::from_csr(&sum), expected);
+    }
+
+    #[test]
+    fn stacking_matches_dense(ds in many_any_size()) {
+        let csrs: Vec<M> = ds.iter().map(Dense::to_csr).collect();
+        let refs: Vec<&M> = csrs.iter().collect();
+
+        let diag = CSR::block_diag(&refs);
+        assert_canonical(&diag);
+        let mut expected = Dense::zeros(
+            ds.iter().map(|d| d.n_rows).sum(),
+            ds.iter().map(|d| d.n_cols).sum(),
+        );
+        let (mut row_off, mut col_off) = (0, 0);
+        for d in &ds {
+            for (r, c, v) in d.triples() {
+                expected.set(row_off + r, col_off + c, v);
+            }
+            row_off += d.n_rows;
+            col_off += d.n_cols;
+        }
+        prop_assert_eq!(Dense::from_csr(&diag), expected);
+
+        let same_cols = ds.iter().all(|d| Some(d.n_cols) == ds.first().map(|f| f.n_cols));
+        match CSR::vstack(&refs) {
+            Ok(v) => {
+                assert_canonical(&v);
+                let data = ds.iter().flat_map(|d| d.data.clone()).collect();
+                prop_assert_eq!(
+                    Dense::from_csr(&v),
+                    Dense { n_rows: v.n_rows, n_cols: ds[0].n_cols, data }
+                );
+            }
+            Err(_) => prop_assert!(ds.is_empty() || !same_cols),
+        }
+
+        let same_rows = ds.iter().all(|d| Some(d.n_rows) == ds.first().map(|f| f.n_rows));
+        match CSR::hstack(&refs) {
+            Ok(h) => {
+                assert_canonical(&h);
+                let mut expected = Dense::zeros(ds[0].n_rows, h.n_cols);
+                let mut col_off = 0;
+                for d in &ds {
+                    for (r, c, v) in d.triples() {
+                        expected.set(r, col_off + c, v);
+                    }
+                    col_off += d.n_cols;
+                }
+                prop_assert_eq!(Dense::from_csr(&h), expected);
+            }
+            Err(_) => prop_assert!(ds.is_empty() || !same_rows),
+        }
+    }
+
+    #[test]
+    fn aligned_view_matches_csr(a in any_dense()) {
+        let csr = a.to_csr();
+        let mut bytes = Vec::new();
+        csr.write_aligned(&mut bytes).unwrap();
+        prop_assert_eq!(bytes.len() % 64, 0);
+
+        // a u64 buffer gives the bytes the alignment a memory map would
+        let mut words = vec![0u64; bytes.len() / 8];
+        bytemuck::cast_slice_mut(&mut words).copy_from_slice(&bytes);
+        let view: CSRView<f64, u32> =
+            CSRView::from_aligned(bytemuck::cast_slice(&words)).unwrap();
+
+        prop_assert_eq!(Dense::from_csr(&view.to_csr()), a.clone());
+        for row in 0..a.n_rows {
+            prop_assert_eq!(
+                view.inter_row(row).collect::<Vec<_>>(),
+                csr.inter_row(row).collect::<Vec<_>>()
+            );
+            for col in 0..a.n_cols + 1 {
+                prop_assert_eq!(view.value(row, col), csr.value(row, col));
+            }
+        }
+        prop_assert_eq!(csr.view().value(a.n_rows, 0), None);
+    }
+
+    #[test]
+    fn aligned_symmetric_view_matches_symmetric(d in symmetric_dense()) {
+        let sym: SymmetricCSR<f64, u32> = SymmetricCSR::from_csr(&d.to_csr()).unwrap();
+        let mut bytes = Vec::new();
+        sym.write_aligned(&mut bytes).unwrap();
+
+        let mut words = vec![0u64; bytes.len() / 8];
+        bytemuck::cast_slice_mut(&mut words).copy_from_slice(&bytes);
+        let view: SymmetricCSRView<f64, u32> = SymmetricCSRView::from_aligned(bytemuck::cast_slice(&words)).unwrap();
+
+        prop_assert_eq!(view.n_stored(), sym.n_stored());
+        prop_assert_eq!(Dense::from_csr(&view.to_symmetric_csr().to_csr()), d.clone());
+        for row in 0..d.n_rows {
+            prop_assert_eq!(
+                view.inter_row(row).collect::<Vec<_>>(),
+                sym.inter_row(row).collect::<Vec<_>>()
+            );
+            for col in 0..d.n_cols {
+                prop_assert_eq!(view.value(row, col), sym.value(row, col));
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_prune_keeps_the_top_k_of_either_row(d in symmetric_dense(), min in -3i32..=3, k in 0..4usize) {
+        let min = f64::from(min);
+        let sym: SymmetricCSR<f64, u32> = SymmetricCSR::from_csr(&d.to_csr()).unwrap();
+        let pruned = sym.prune(min, Some(k));
+
+        // an entry survives where pruning the full rows keeps either half
+        let by_rows = Dense::from_csr(&d.to_csr().prune(min, Some(k)));
+        let expected = by_rows.zip(&by_rows.transpose(), |x, y| if x != 0.0 { x } else { y });
+        prop_assert_eq!(Dense::from_csr(&pruned.to_csr()), expected);
+        prop_assert_eq!(
+            Dense::from_csr(&sym.prune(min, None).to_csr()),
+            Dense::from_csr(&d.to_csr().prune(min, None))
+        );
+    }
+}
+
+#[test]
+fn unsigned_sub_reports_underflow() {
+    let a: CSR<u32, u32> = CSR::from_triples(&[(0, 0, 5), (1, 2, 1)], 2, 3);
+    let b: CSR<u32, u32> = CSR::from_triples(&[(0, 0, 2), (1, 2, 1)], 2, 3);
+    let diff = (&a - &b).unwrap();
+    assert_eq!(diff.iter().collect::<Vec<_>>(), vec![(0, 0, 3)]);
+
+    // a cell missing from the left-hand side
+    let c: CSR<u32, u32> = CSR::from_triples(&[(0, 1, 1)], 2, 3);
+    assert!(matches!(&a - &c, Err(Error::Underflow { row: 0, col: 1 })));
+    // a stored cell that is smaller
+    assert!(matches!(&b - &a, Err(Error::Underflow { row: 0, col: 0 })));
+}
+
+#[test]
+fn aligned_view_rejects_bad_input() {
+    let csr: CSR<f32, u32> = CSR::from_triples(&[(0, 1, 2.0), (1, 0, -1.0)], 2, 2);
+    let mut bytes = Vec::new();
+    csr.write_aligned(&mut bytes).unwrap();
+
+    let mut words = vec![0u64; bytes.len() / 8 + 1];
+    let aligned: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    aligned[..bytes.len()].copy_from_slice(&bytes);
+    let with = |edits: &[(usize, &[u8])]| {
+        let mut words = vec![0u64; bytes.len() / 8];
+        let corrupt: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+        corrupt.copy_from_slice(&bytes);
+        for &(at, edit) in edits {
+            corrupt[at..at + edit.len()].copy_from_slice(edit);
+        }
+        words
+    };
+    // `row_ptr` is at 64 and holds [0, 1, 2], `col_idx` is at 128 and holds [1, 0]
+    let bad = [
+        // decreasing row pointers
+        with(&[(68, &3u32.to_le_bytes())]),
+        // a column out of bounds
+        with(&[(128, &2u32.to_le_bytes())]),
+        // unsorted columns in one row
+        with(&[(68, &2u32.to_le_bytes()), (128, &1u32.to_le_bytes())]),
+        // n_rows so large the layout overflows
+        with(&[(8, &u64::MAX.to_le_bytes())]),
+    ];
+    for words in &bad {
+        assert!(CSRView::<f32, u32>::from_aligned(bytemuck::cast_slice(words)).is_err());
+    }
+    // only the structure is wrong, which the trusted view does not check
+    let trusted = CSRView::<f32, u32>::from_aligned_trusted(bytemuck::cast_slice(&bad[2])).unwrap();
+    assert!(ColumnIndex::new(trusted).is_err());
+
+    assert!(CSRView::<f32, u32>::from_aligned(aligned).is_ok());
+    assert!(CSRView::<f64, u32>::from_aligned(aligned).is_err());
+    assert!(CSRView::<f32, u64>::from_aligned(aligned).is_err());
+    assert!(CSRView::<f32, u32>::from_aligned(&aligned[..bytes.len() - 64]).is_err());
+
+    aligned.copy_within(..bytes.len(), 1);
+    assert!(CSRView::<f32, u32>::from_aligned(&aligned[1..]).is_err());
+}
+
+#[test]
+fn parts_are_checked() {
+    let parts = |row_ptr: &[u32], col_idx: &[u32]| {
+        let val = vec![1.0f32; col_idx.len()];
+        CSR::try_from_parts(2, 2, row_ptr.to_vec(), col_idx.to_vec(), val)
+    };
+    let csr = parts(&[0, 1, 2], &[1, 0]).unwrap();
+    assert_eq!(csr.iter().collect::<Vec<_>>(), [(0, 1, 1.0), (1, 0, 1.0)]);
+
+    // row pointers past the end of the columns
+    assert!(parts(&[0, 5, 5], &[1]).is_err());
+    // too few row pointers
+    assert!(parts(&[0, 1], &[1]).is_err());
+    // decreasing row pointers
+    assert!(parts(&[0, 2, 1], &[0, 1]).is_err());
+    // unsorted and out of bounds columns
+    assert!(parts(&[0, 2, 2], &[1, 0]).is_err());
+    assert!(parts(&[0, 1, 1], &[2]).is_err());
+    // values and columns differ in length
+    assert!(CSR::<f32, u32>::try_from_parts(2, 2, vec![0, 1, 1], vec![0], vec![]).is_err());
+}
+
+#[test]
+fn aligned_symmetric_view_checks_its_index() {
+    let sym: SymmetricCSR<f32, u32> = SymmetricCSR::from_triples(&[(0, 1, 2.0), (0, 2, 1.0), (1, 1, 3.0)], 3);
+    let mut bytes = Vec::new();
+    sym.write_aligned(&mut bytes).unwrap();
+    let mut words = vec![0u64; bytes.len() / 8];
+    bytemuck::cast_slice_mut(&mut words).copy_from_slice(&bytes);
+    assert!(SymmetricCSRView::<f32, u32>::from_aligned(bytemuck::cast_slice(&words)).is_ok());
+
+    // the upper triangle takes 256 bytes; the index header follows, then
+    // `col_ptr` holding [0, 0, 1, 2] and `below` holding [0, 1]
+    let below_at = 256 + 128;
+    let corrupt: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+    corrupt[below_at..below_at + 4].copy_from_slice(&1u32.to_le_bytes());
+    assert!(SymmetricCSRView::<f32, u32>::from_aligned_trusted(bytemuck::cast_slice(&words)).is_ok());
+    assert!(SymmetricCSRView::<f32, u32>::from_aligned(bytemuck::cast_slice(&words)).is_err());
+
+    // an index of the wrong size
+    let truncated = &bytemuck::cast_slice::<u64, u8>(&words)[..below_at];
+    assert!(SymmetricCSRView::<f32, u32>::from_aligned_trusted(truncated).is_err());
+}
+
+#[test]
+fn symmetric_from_fxhash_sums_both_orders() {
+    let map: FxHashMap<(usize, usize), u32> = [((0, 1), 2), ((1, 0), 3), ((2, 1), 4), ((2, 2), 5)].into_iter().collect();
+    let sym: SymmetricCSR<u32, u32> = SymmetricCSR::from_fxhash(&map, 3);
+
+    assert_eq!(sym.upper().iter().collect::<Vec<_>>(), [(0, 1, 5), (1, 2, 4), (2, 2, 5)]);
+    assert_eq!(sym.value(1, 0), Some(5));
+}
@@ -0,0 +1,138 @@
+//! Matrix Market and npz files: round trips through the dense reference, and
+//! files as SciPy reads and writes them.
+
+mod common;
+
+use common::*;
+use csr_matrix::{SymmetricCSR, CSR};
+use proptest::prelude::*;
+use std::io::{Cursor, Read, Write as _};
+
+proptest! {
+    #[test]
+    fn mtx_round_trips(a in any_dense(), d in symmetric_dense()) {
+        let mut buf = Vec::new();
+        a.to_csr().write_mtx(&mut buf).unwrap();
+        let read: M = CSR::read_mtx(buf.as_slice()).unwrap();
+        assert_canonical(&read);
+        prop_assert_eq!(Dense::from_csr(&read), a);
+
+        let sym: SymmetricCSR<f64, u32> = SymmetricCSR::from_csr(&d.to_csr()).unwrap();
+        let mut buf = Vec::new();
+        sym.write_mtx(&mut buf).unwrap();
+        let full: M = CSR::read_mtx(buf.as_slice()).unwrap();
+        prop_assert_eq!(Dense::from_csr(&full), d.clone());
+        let read: SymmetricCSR<f64, u32> = SymmetricCSR::read_mtx(buf.as_slice()).unwrap();
+        prop_assert_eq!(Dense::from_csr(&read.to_csr()), d);
+    }
+
+    #[test]
+    fn npz_round_trips(a in any_dense()) {
+        let mut buf = Cursor::new(Vec::new());
+        a.to_csr().write_npz(&mut buf).unwrap();
+        let read: M = CSR::read_npz(buf).unwrap();
+        assert_canonical(&read);
+        prop_assert_eq!(Dense::from_csr(&read), a);
+    }
+}
+
+#[test]
+fn mtx_reads_pattern_and_rejects_bad_files() {
+    let pattern = "%%MatrixMarket matrix coordinate pattern general\n% comment\n2 3 2\n1 3\n2 1\n";
+    let m: CSR<u32, u32> = CSR::read_mtx(pattern.as_bytes()).unwrap();
+    assert_eq!(m.iter().collect::<Vec<_>>(), vec![(0, 2, 1), (1, 0, 1)]);
+
+    let bad = [
+        "%%MatrixMarket matrix array real general\n1 1\n1.0\n",
+        "%%MatrixMarket matrix coordinate real general\n2 2 1\n1 1 0.5\n",
+        "%%MatrixMarket matrix coordinate integer general\n2 2 2\n1 1 1\n",
+        "%%MatrixMarket matrix coordinate integer general\n2 2 1\n3 1 1\n",
+    ];
+    for file in bad {
+        assert!(CSR::<u32, u32>::read_mtx(file.as_bytes()).is_err(), "{file}");
+    }
+}
+
+/// Builds an npz file the way SciPy does, with arbitrary dtypes and entries.
+fn scipy_npz(arrays: &[(&str, &str, &str, Vec<u8>)], method: zip::CompressionMethod) -> Cursor<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, descr, shape, data) in arrays {
+        let options = zip::write::SimpleFileOptions::default().compression_method(method);
+        zip.start_file(format!("{name}.npy"), options).unwrap();
+        let dict = format!("{{'descr': '{descr}', 'fortran_order': False, 'shape': {shape}, }}\n");
+        zip.write_all(b"\x93NUMPY\x01\x00").unwrap();
+        zip.write_all(&(dict.len() as u16).to_le_bytes()).unwrap();
+        zip.write_all(dict.as_bytes()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap()
+}
+
+fn le_bytes<const N: usize>(values: impl IntoIterator<Item = [u8; N]>) -> Vec<u8> {
+    values.into_iter().flatten().collect()
+}
+
+#[test]
+fn npz_reads_scipy_files() {
+    // unsorted and duplicate indices, an explicit zero, int64 index arrays
+    let arrays = [
+        ("format", "|S3", "()", b"csr".to_vec()),
+        ("shape", "<i8", "(2,)", le_bytes([2i64, 3].map(i64::to_le_bytes))),
+        ("indptr", "<i8", "(3,)", le_bytes([0i64, 3, 4].map(i64::to_le_bytes))),
+        ("indices", "<i8", "(4,)", le_bytes([2i64, 0, 2, 1].map(i64::to_le_bytes))),
+        ("data", "<f4", "(4,)", le_bytes([1.5f32, 2.0, 1.0, 0.0].map(f32::to_le_bytes))),
+    ];
+    for method in [zip::CompressionMethod::Stored, zip::CompressionMethod::Deflated] {
+        let m: CSR<f32, u32> = CSR::read_npz(scipy_npz(&arrays, method)).unwrap();
+        assert_eq!(m.iter().collect::<Vec<_>>(), vec![(0, 0, 2.0), (0, 2, 2.5)]);
+        assert_eq!((m.n_rows, m.n_cols), (2, 3));
+    }
+
+    // wrong value dtype
+    assert!(CSR::<f64, u32>::read_npz(scipy_npz(&arrays, zip::CompressionMethod::Stored)).is_err());
+
+    // column out of bounds, and a CSC file
+    let mut bad = arrays.clone();
+    bad[3].3 = le_bytes([2i64, 0, 3, 1].map(i64::to_le_bytes));
+    assert!(CSR::<f32, u32>::read_npz(scipy_npz(&bad, zip::CompressionMethod::Stored)).is_err());
+    let mut csc = arrays.clone();
+    csc[0].3 = b"csc".to_vec();
+    assert!(CSR::<f32, u32>::read_npz(scipy_npz(&csc, zip::CompressionMethod::Stored)).is_err());
+}
+
+#[test]
+fn npz_writes_scipy_layout() {
+    let m: CSR<f64, u32> = CSR::from_triples(&[(0, 1, 2.0), (2, 0, -1.0)], 3, 2);
+    let mut buf = Cursor::new(Vec::new());
+    m.write_npz(&mut buf).unwrap();
+
+    let mut zip = zip::ZipArchive::new(buf).unwrap();
+    let mut names: Vec<_> = zip.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, ["data.npy", "format.npy", "indices.npy", "indptr.npy", "shape.npy"]);
+
+    let mut read = |name: &str| {
+        let mut file = zip.by_name(name).unwrap();
+        assert_eq!(file.compression(), zip::CompressionMethod::Stored);
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).unwrap();
+        // data is 64-byte aligned behind a version 1.0 header
+        let header_len = 10 + u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!(header_len % 64, 0);
+        let header = String::from_utf8(bytes[10..header_len].to_vec()).unwrap();
+        (header, bytes[header_len..].to_vec())
+    };
+
+    let (header, data) = read("indptr.npy");
+    assert!(header.starts_with("{'descr': '<i4', 'fortran_order': False, 'shape': (4,), }"));
+    assert_eq!(data, le_bytes([0i32, 1, 1, 2].map(i32::to_le_bytes)));
+    let (header, data) = read("format.npy");
+    assert!(header.starts_with("{'descr': '|S3', 'fortran_order': False, 'shape': (), }"));
+    assert_eq!(data, b"csr");
+    let (header, data) = read("shape.npy");
+    assert!(header.contains("'descr': '<i8'"));
+    assert_eq!(data, le_bytes([3i64, 2].map(i64::to_le_bytes)));
+    let (header, data) = read("data.npy");
+    assert!(header.contains("'descr': '<f8'"));
+    assert_eq!(data, le_bytes([2.0f64, -1.0].map(f64::to_le_bytes)));
+}
@@ -0,0 +1,128 @@
+//! In-place writes: `insert`, `remove`, `update` and batched writes.
+
+use csr_matrix::{Error, CSR};
+
+fn dense(csr: &CSR) -> Vec<Vec<f32>> {
+    (0..csr.n_rows).map(|row| csr.get_row(row).unwrap()).collect()
+}
+
+#[test]
+fn insert_returns_previous_value() {
+    let mut csr: CSR = CSR::new(3, 4);
+    assert_eq!(csr.insert(1, 2, 5.0).unwrap(), None);
+    assert_eq!(csr.insert(1, 0, 1.0).unwrap(), None);
+    assert_eq!(csr.insert(2, 3, 2.0).unwrap(), None);
+    assert_eq!(csr.insert(1, 2, 7.0).unwrap(), Some(5.0));
+
+    assert_eq!(csr.n_nz, 3);
+    assert_eq!(
+        dense(&csr),
+        vec![vec![0.0; 4], vec![1.0, 0.0, 7.0, 0.0], vec![0.0, 0.0, 0.0, 2.0]]
+    );
+}
+
+#[test]
+fn inserting_zero_removes() {
+    let mut csr: CSR = CSR::new(2, 2);
+    csr.insert(0, 1, 3.0).unwrap();
+    assert_eq!(csr.insert(0, 1, 0.0).unwrap(), Some(3.0));
+    assert_eq!(csr.n_nz, 0);
+    assert_eq!(csr.value(0, 1), Some(0.0));
+}
+
+#[test]
+fn remove_returns_stored_value() {
+    let mut csr: CSR = CSR::new(2, 3);
+    csr.insert(0, 0, 1.0).unwrap();
+    csr.insert(0, 2, 2.0).unwrap();
+    csr.insert(1, 1, 3.0).unwrap();
+
+    assert_eq!(csr.remove(0, 2).unwrap(), Some(2.0));
+    assert_eq!(csr.remove(0, 2).unwrap(), None);
+    assert_eq!(csr.remove(1, 0).unwrap(), None);
+    assert_eq!(csr.n_nz, 2);
+    assert_eq!(dense(&csr), vec![vec![1.0, 0.0, 0.0], vec![0.0, 3.0, 0.0]]);
+}
+
+#[test]
+fn update_reads_missing_as_zero() {
+    let mut csr: CSR = CSR::new(2, 2);
+    assert_eq!(csr.update(1, 1, |v| v + 2.0).unwrap(), 2.0);
+    assert_eq!(csr.update(1, 1, |v| v * 3.0).unwrap(), 6.0);
+    assert_eq!(csr.update(1, 1, |_| 0.0).unwrap(), 0.0);
+    assert_eq!(csr.n_nz, 0);
+}
+
+#[test]
+fn out_of_bounds_writes_are_errors() {
+    let mut csr: CSR = CSR::new(2, 2);
+    csr.insert(0, 0, 1.0).unwrap();
+
+    assert!(matches!(
+        csr.insert(2, 0, 1.0),
+        Err(Error::OutOfBounds { row: 2, col: 0, n_rows: 2, n_cols: 2 })
+    ));
+    assert!(matches!(csr.remove(0, 2), Err(Error::OutOfBounds { .. })));
+    assert!(matches!(csr.update(5, 5, |v| v + 1.0), Err(Error::OutOfBounds { .. })));
+    assert_eq!(dense(&csr), vec![vec![1.0, 0.0], vec![0.0, 0.0]]);
+}
+
+#[test]
+fn batch_matches_single_writes() {
+    let writes = [(0, 1, 1.0), (2, 0, 4.0), (0, 1, 2.0), (1, 1, 3.0), (2, 0, 0.0), (0, 0, 5.0)];
+
+    let mut single: CSR = CSR::new(3, 2);
+    single.insert(2, 1, 9.0).unwrap();
+    let mut batched = single.clone();
+
+    for &(row, col, val) in &writes {
+        single.insert(row, col, val).unwrap();
+    }
+    let mut batch = batched.batch();
+    for &(row, col, val) in &writes {
+        batch.insert(row, col, val);
+    }
+    batch.remove(2, 1);
+    batch.commit().unwrap();
+    single.remove(2, 1).unwrap();
+
+    assert_eq!(batched.n_nz, single.n_nz);
+    assert_eq!(dense(&batched), dense(&single));
+}
+
+#[test]
+fn batch_last_write_wins() {
+    let mut csr: CSR = CSR::new(1, 1);
+    let mut batch = csr.batch();
+    batch.insert(0, 0, 1.0);
+    batch.remove(0, 0);
+    batch.insert(0, 0, 3.0);
+    assert_eq!(batch.len(), 3);
+    batch.commit().unwrap();
+
+    assert_eq!(csr.value(0, 0), Some(3.0));
+}
+
+#[test]
+fn batch_out_of_bounds_is_an_error_and_writes_nothing() {
+    let mut csr: CSR = CSR::new(2, 2);
+    let mut batch = csr.batch();
+    batch.insert(0, 0, 1.0);
+    batch.insert(1, 2, 2.0);
+
+    assert!(matches!(
+        batch.commit(),
+        Err(Error::OutOfBounds { row: 1, col: 2, n_rows: 2, n_cols: 2 })
+    ));
+    assert_eq!(csr.n_nz, 0);
+}
+
+#[test]
+fn dropped_batch_discards_writes() {
+    let mut csr: CSR = CSR::new(2, 2);
+    let mut batch = csr.batch();
+    batch.insert(0, 0, 1.0);
+    drop(batch);
+
+    assert_eq!(csr.n_nz, 0);
+}
@@ -0,0 +1,92 @@
+use crate::read_write::ModelError;
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// A posts CSV could not be parsed.
+    Csv(csv::Error),
+    /// A tag that is not in the vocabulary.
+    UnknownTag(String),
+    /// A tag whose chain of aliases loops back on itself.
+    AliasCycle(String),
+    /// A tag that implies itself through a chain of implications.
+    ImplicationCycle(String),
+    /// More posts than tag counts can hold.
+    TooManyPosts(usize),
+    /// Matrices of mismatched dimensions were combined, a matrix did not
+    /// have the shape an operation needs, or a matrix file could not be
+    /// parsed.
+    Matrix(csr_matrix::Error),
+    /// A model file could not be read: it is not a model, is of an
+    /// unsupported or legacy version, or is corrupt.
+    Model(ModelError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Csv(e) => write!(f, "Invalid posts CSV: {e}"),
+            Error::UnknownTag(tag) => write!(f, "Unknown tag '{tag}'"),
+            Error::AliasCycle(tag) => write!(f, "Aliases of tag '{tag}' form a cycle"),
+            Error::ImplicationCycle(tag) => write!(f, "Tag '{tag}' implies itself"),
+            Error::TooManyPosts(n_posts) => write!(f, "{n_posts} posts are more than tag counts can hold"),
+            Error::Matrix(e) => write!(f, "{e}"),
+            Error::Model(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Csv(e) => Some(e),
+            Error::Matrix(e) => Some(e),
+            Error::Model(e) => Some(e),
+            Error::UnknownTag(_)
+            | Error::AliasCycle(_)
+            | Error::ImplicationCycle(_)
+            | Error::TooManyPosts(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Error {
+        if !e.is_io_error() {
+            return Error::Csv(e);
+        }
+        match e.into_kind() {
+            csv::ErrorKind::Io(e) => Error::Io(e),
+            _ => unreachable!("checked to be an I/O error"),
+        }
+    }
+}
+
+/// I/O errors keep their kind. Errors viewing the matrix of a model are
+/// turned into [`ModelError::Corrupt`] where the model is read.
+impl From<csr_matrix::Error> for Error {
+    fn from(e: csr_matrix::Error) -> Error {
+        match e {
+            csr_matrix::Error::Io(e) => Error::Io(e),
+            e => Error::Matrix(e),
+        }
+    }
+}
+
+impl From<ModelError> for Error {
+    fn from(e: ModelError) -> Error {
+        Error::Model(e)
+    }
+}
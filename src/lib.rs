@@ -0,0 +1,5 @@
+pub mod association;
+pub mod error;
+pub mod nmpi;
+pub mod read_write;
+pub mod tags;
@@ -1,49 +1,130 @@
-use csv::ReaderBuilder;
-use std::error::Error;
-use std::string::String;
+use std::path::Path;
 use std::time::Instant;
 use rand::prelude::*;
-use rand::distr::weighted;
 
-use csr_matrix::CSR;
 use rand::distr::weighted::WeightedIndex;
-use rand::{rng, thread_rng};
-use crate::nmpi::{get_npmi_matrix, get_co_count_matrix, get_most_related_tags, get_npmi};
-use crate::read_write::{write_data, read_posts, read_data};
+use rand::rng;
+use prompt_get::association::{Measure, Smoothing};
+use prompt_get::error::{Error, Result};
+use prompt_get::nmpi::{
+    get_co_count_matrix, get_most_related_tags, get_smoothed_npmi_matrix, mine_aliases, mine_implications,
+    prune_association_matrix, MiningParams,
+};
+use prompt_get::read_write::{
+    read_aliases, read_implications, read_posts_with, read_tag_categories, write_model, write_suggestions,
+    BuildParams, ImpliedTags, MappedModel, PostsOptions, PostsTagIdxs,
+};
+use prompt_get::tags::Tags;
 
-mod nmpi;
-mod read_write;
-mod tags;
+fn main() {
+    let result = match std::env::args().nth(1).as_deref() {
+        Some("build") => build(),
+        Some("suggest") => suggest(),
+        _ => generate(),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
 
+/// Reads `path` with `read`, or gives an empty default if there is no such
+/// file.
+fn read_optional<T: Default>(path: &str, read: fn(&str) -> Result<T>) -> Result<T> {
+    if Path::new(path).exists() {
+        read(path)
+    } else {
+        Ok(T::default())
+    }
+}
 
+/// Reads the posts dump with the tag metadata there is.
+fn read_posts() -> Result<(usize, Tags, PostsTagIdxs)> {
+    let options = PostsOptions {
+        aliases: read_optional("data/tag_aliases.csv", read_aliases)?,
+        implications: read_optional("data/tag_implications.csv", read_implications)?,
+        implied: ImpliedTags::Strip,
+        categories: read_optional("data/tags.csv", read_tag_categories)?,
+    };
+    read_posts_with("data/posts-2025-09-27.csv", &options)
+}
 
-fn main() {
-    let mut start = Instant::now();
-    // print!("Reading posts csv...", );
-    // let (n_posts, tags, posts_tags_idxs) = read_posts("data/posts-2025-09-27.csv").unwrap();
-    // println!("\t\t\t\t[Done in {:?}]", start.elapsed());
-    //
-    // start = Instant::now();
-    // print!("Calculating co count matrix...");
-    // let co_counts_matrix = get_co_count_matrix(n_posts, &tags, posts_tags_idxs).unwrap();
-    // println!("\t\t\t[Done in {:?}]", start.elapsed());
-    //
-    // start = Instant::now();
-    // print!("Calculating npmi matrix...");
-    // let npmi_matrix = get_npmi_matrix(n_posts, &tags, &co_counts_matrix);
-    // println!("\t\t\t[Done in {:?}]", start.elapsed());
-    //
-    // start = Instant::now();
-    // print!("Writing data...");
-    // write_data("data/npmi", n_posts, &tags, &npmi_matrix);
-    // println!("\t\t\t\t\t[Done in {:?}]", start.elapsed());
-
-    start = Instant::now();
+/// Builds `data/npmi.model` from the posts dump.
+fn build() -> Result<()> {
+    let start = Instant::now();
+    print!("Reading posts csv...");
+    let (n_posts, tags, posts_tags_idxs) = read_posts()?;
+    println!("\t\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Calculating co count matrix...");
+    let co_counts_matrix = get_co_count_matrix(n_posts, &tags, posts_tags_idxs)?;
+    println!("\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Calculating npmi matrix...");
+    let params = BuildParams {
+        measure: Measure::Npmi,
+        smoothing: Smoothing { min_pair_count: 2, additive: 1.0, alpha: 0.75, ..Default::default() },
+        min_score: Some(0.05),
+        top_k: Some(200),
+    };
+    let npmi_matrix = get_smoothed_npmi_matrix(n_posts, &tags, &co_counts_matrix, params.smoothing)?;
+    println!("\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Pruning npmi matrix...");
+    let npmi_matrix = prune_association_matrix(&npmi_matrix, &params);
+    println!("\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Writing data...");
+    write_model("data/npmi.model", params, n_posts, &tags, &npmi_matrix)?;
+    println!("\t\t\t\t\t[Done in {:?}]", start.elapsed());
+
+    Ok(())
+}
+
+/// Writes implications and aliases mined from the posts dump to
+/// `data/suggestions.csv` for review. Known ones are already applied to the
+/// posts, so they are not suggested again.
+fn suggest() -> Result<()> {
+    let start = Instant::now();
+    print!("Reading posts csv...");
+    let (n_posts, tags, posts_tags_idxs) = read_posts()?;
+    println!("\t\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Calculating co count matrix...");
+    let co_counts_matrix = get_co_count_matrix(n_posts, &tags, posts_tags_idxs)?;
+    println!("\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Mining suggestions...");
+    let params = MiningParams { min_support: 20, min_confidence: 0.95, min_similarity: 0.9 };
+    let implications = mine_implications(&tags, &co_counts_matrix, params)?;
+    let aliases = mine_aliases(&tags, &co_counts_matrix, params)?;
+    println!("\t\t\t\t[Done in {:?}]", start.elapsed());
+
+    let start = Instant::now();
+    print!("Writing suggestions...");
+    write_suggestions("data/suggestions.csv", &tags, &implications, &aliases)?;
+    println!("\t\t\t\t[Done in {:?}]", start.elapsed());
+
+    Ok(())
+}
+
+/// Prints prompts grown from a seed tag by sampling related tags.
+fn generate() -> Result<()> {
+    let start = Instant::now();
     print!("Reading data...");
-    let (n_posts, tags, npmi_matrix) = read_data("data/npmi").unwrap();
+    let model = MappedModel::open("data/npmi.model")?;
+    let (tags, npmi_matrix) = (model.tags(), model.npmi_matrix());
+    let aliases = read_optional("data/tag_aliases.csv", read_aliases)?;
+    let implications = read_optional("data/tag_implications.csv", read_implications)?.resolve_aliases(&aliases);
     println!("\t\t\t\t\t[Done in {:?}]", start.elapsed());
 
-    // let related: Vec<(&str, f32)> = get_most_related_tags(10, &npmi_matrix, vec![tags.get_idx("loimu").unwrap() as u32])
+    // let related: Vec<(&str, f32)> = get_most_related_tags(10, npmi_matrix, tags, vec![tags.get_idx("loimu").unwrap() as u32], Some(&[Category::General]))
     //     .iter().map(|(i, s)| (tags.get_name(*i as usize).unwrap(), *s)).collect();
     // println!("{:?}", related);
 
@@ -51,19 +132,28 @@ fn main() {
         let mut prompt_tag: Vec<&str> = vec!["regina_(huffslove)"];
         for _ in 0..10 {
             let idxs = prompt_tag.iter()
-                .map(|t| tags.get_idx(t).unwrap() as u32)
-                .collect();
-            let tag_scores = get_most_related_tags(15, &npmi_matrix, idxs);
+                .map(|t| tags.get_idx(t).map(|idx| idx as u32).ok_or_else(|| Error::UnknownTag(t.to_string())))
+                .collect::<Result<_>>()?;
+            let tag_scores = get_most_related_tags(15, npmi_matrix, tags, idxs, None);
+            // a tag implied by one already in the prompt adds nothing
+            let implied: Vec<&str> = prompt_tag.iter().flat_map(|t| implications.implied(t)).collect();
             let mut tag_idsx: Vec<u32> = Vec::new();
             let mut weights: Vec<f32> = Vec::new();
             for (idx, w) in tag_scores {
+                if implied.contains(&tags.get_name(idx as usize).unwrap()) {
+                    continue;
+                }
                 tag_idsx.push(idx);
                 weights.push(w);
             }
-            let dist = WeightedIndex::new(weights).unwrap();
+            let Ok(dist) = WeightedIndex::new(weights) else {
+                break;
+            };
             let mut rng = rng();
             prompt_tag.push(tags.get_name(tag_idsx[dist.sample(&mut rng)] as usize).unwrap());
         }
         println!("{:?}", prompt_tag);
     }
+
+    Ok(())
 }
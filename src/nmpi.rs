@@ -1,99 +1,292 @@
-use std::arch::x86_64::_mm256_min_pd;
-use std::cmp::Ordering::Greater;
-use csr_matrix::CSR;
-use rustc_hash::FxHashMap;
-use std::error::Error;
+use csr_matrix::{CSRBuilder, CSRView, SymmetricCSR, SymmetricCSRView, CSC, CSR};
+use serde::{Deserialize, Serialize};
 use std::thread;
-use bincode::decode_from_reader;
-use itertools::Itertools;
-use crate::tags::Tags;
+use itertools::{Either, Itertools};
+use crate::association::{AssociationMeasure, Npmi, PairCounts, SmoothedNpmi, Smoothing};
+use crate::error::{Error, Result};
+use crate::read_write::BuildParams;
+use crate::tags::{Category, Tags};
 
-pub fn   get_co_count_matrix(
+/// Pair co-occurrence counts, indexed by tag index on both axes. Counts are
+/// symmetric, so only the upper triangle is stored.
+pub type CoCountMatrix = SymmetricCSR<u32, u32>;
+
+/// Scores of pairs of tags, indexed by tag index on both axes, see
+/// [`get_association_matrix`]. Scores of a symmetric measure such as NPMI
+/// are stored once per pair, as the upper triangle; others in full.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AssociationMatrix {
+    Symmetric(SymmetricCSR<f32, u32>),
+    Full(CSR<f32, u32>),
+}
+
+/// An [`AssociationMatrix`] borrowed from memory or from a memory-mapped model.
+#[derive(Debug, Clone, Copy)]
+pub enum AssociationView<'a> {
+    Symmetric(SymmetricCSRView<'a, f32, u32>),
+    Full(CSRView<'a, f32, u32>),
+}
+
+impl AssociationMatrix {
+    pub fn view(&self) -> AssociationView<'_> {
+        match self {
+            AssociationMatrix::Symmetric(matrix) => AssociationView::Symmetric(matrix.view()),
+            AssociationMatrix::Full(matrix) => AssociationView::Full(matrix.view()),
+        }
+    }
+
+    pub fn n_tags(&self) -> usize {
+        self.view().n_tags()
+    }
+
+    /// See [`AssociationView::n_stored`].
+    pub fn n_stored(&self) -> usize {
+        self.view().n_stored()
+    }
+
+    pub fn value(&self, row: usize, col: usize) -> Option<f32> {
+        self.view().value(row, col)
+    }
+
+    /// Every score, both halves of a symmetric matrix included.
+    pub fn to_csr(&self) -> CSR<f32, u32> {
+        match self {
+            AssociationMatrix::Symmetric(matrix) => matrix.to_csr(),
+            AssociationMatrix::Full(matrix) => matrix.clone(),
+        }
+    }
+}
+
+impl<'a> AssociationView<'a> {
+    pub fn n_tags(&self) -> usize {
+        match self {
+            AssociationView::Symmetric(matrix) => matrix.n_rows(),
+            AssociationView::Full(matrix) => matrix.n_rows,
+        }
+    }
+
+    /// Number of scores stored, which for a symmetric matrix is one per
+    /// pair.
+    pub fn n_stored(&self) -> usize {
+        match self {
+            AssociationView::Symmetric(matrix) => matrix.n_stored(),
+            AssociationView::Full(matrix) => matrix.n_nz,
+        }
+    }
+
+    pub fn value(&self, row: usize, col: usize) -> Option<f32> {
+        match self {
+            AssociationView::Symmetric(matrix) => matrix.value(row, col),
+            AssociationView::Full(matrix) => matrix.value(row, col),
+        }
+    }
+
+    /// Iterates over the scores of `row` as `(col, score)` with columns
+    /// ascending.
+    pub fn inter_row(&self, row: usize) -> impl Iterator<Item = (usize, f32)> + 'a {
+        match self {
+            AssociationView::Symmetric(matrix) => Either::Left(matrix.inter_row(row)),
+            AssociationView::Full(matrix) => Either::Right(matrix.inter_row(row)),
+        }
+    }
+
+    pub fn to_matrix(&self) -> AssociationMatrix {
+        match self {
+            AssociationView::Symmetric(matrix) => AssociationMatrix::Symmetric(matrix.to_symmetric_csr()),
+            AssociationView::Full(matrix) => AssociationMatrix::Full(matrix.to_csr()),
+        }
+    }
+}
+
+pub fn get_co_count_matrix(
     n_posts: usize,
     tags: &Tags,
     posts_tag_idxs: Vec<Vec<u32>>,
-) -> Result<(CSR), Box<dyn Error>> {
+) -> Result<CoCountMatrix> {
     const N_CHUNCK: usize = 12;
+    // pairs buffered per thread before they are summed into its partial matrix
+    const MAX_BUFFERED_PAIRS: usize = 1 << 22;
 
     let n_tags = tags.len();
-    let mut handles = Vec::new();
-    for chunk in posts_tag_idxs.chunks((n_posts / N_CHUNCK) + 1) {
-        let chunk = chunk.to_vec();
-        let handle = thread::spawn(move || {
-            let mut co_counts: FxHashMap<(usize, usize), f32> = FxHashMap::default();
-
-            for post_tag_idxs in chunk {
-                for i in 0..post_tag_idxs.len() {
-                    for j in (i + 1)..post_tag_idxs.len() {
-                        let a = post_tag_idxs[i] as usize;
-                        let b = post_tag_idxs[j] as usize;
-                        *co_counts.entry((a, b)).or_insert(0.0) += 1.0;
-                        *co_counts.entry((b, a)).or_insert(0.0) += 1.0;
+    // workers borrow their chunk of posts instead of copying it
+    let slices: Vec<CSR<u32, u32>> = thread::scope(|scope| {
+        let handles: Vec<_> = posts_tag_idxs
+            .chunks((n_posts / N_CHUNCK) + 1)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut co_counts: CSRBuilder<u32, u32> =
+                        CSRBuilder::new(n_tags, n_tags).max_buffered(MAX_BUFFERED_PAIRS);
+
+                    for post_tag_idxs in chunk {
+                        for i in 0..post_tag_idxs.len() {
+                            for j in (i + 1)..post_tag_idxs.len() {
+                                let a = post_tag_idxs[i] as usize;
+                                let b = post_tag_idxs[j] as usize;
+                                co_counts.push(a.min(b), a.max(b), 1);
+                            }
+                        }
                     }
-                }
-            }
 
-            let local_co_count_matrix = CSR::from_fxhash(&co_counts, n_tags, n_tags);
-            local_co_count_matrix
-        });
-        handles.push(handle);
-    }
+                    co_counts.build()
+                })
+            })
+            .collect();
 
-    let mut co_count_matrix = CSR::new(n_tags, n_tags);
-    for (i, handle) in handles.into_iter().enumerate() {
-        let slice = handle.join().unwrap();
-        co_count_matrix.add_in_place(&slice)?;
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    });
+    if slices.is_empty() {
+        return Ok(CoCountMatrix::new(n_tags));
     }
+    let upper = CSR::merge_add(&slices.iter().collect::<Vec<_>>())?;
 
-    Ok(co_count_matrix)
+    Ok(CoCountMatrix::from_upper(upper)?)
 }
 
-pub fn get_npmi(tag_a: &str, tag_b: &str, n_posts: u32, co_cout_matrix: &CSR, tags: &Tags) -> f32 {
-    let idx_a = tags.get_idx(tag_a).unwrap();
-    let idx_b = tags.get_idx(tag_b).unwrap();
-    let p_a  = tags.get_count(tag_a).unwrap() as f32 / n_posts as f32;
-    let p_b  =tags.get_count(tag_b).unwrap() as f32 / n_posts as f32;
-    let p_ab = co_cout_matrix.value(idx_a, idx_b).unwrap() / n_posts as f32;
+/// [`Npmi`] of `tag_a` and `tag_b`. Tags that never appear together score
+/// -1, the bound NPMI tends to as their co-count does. Fails if either tag
+/// is unknown or `n_posts` does not fit in the counts.
+pub fn get_npmi(tag_a: &str, tag_b: &str, n_posts: usize, co_count_matrix: &CoCountMatrix, tags: &Tags) -> Result<f32> {
+    let n_posts = PairCounts::checked_n_posts(n_posts)?;
+    let (idx_a, c_a) = known_tag(tags, tag_a)?;
+    let (idx_b, c_b) = known_tag(tags, tag_b)?;
+    let c_ab = co_count_matrix.value(idx_a, idx_b).ok_or_else(|| {
+        Error::Matrix(csr_matrix::Error::DimensionMismatch {
+            lhs: (co_count_matrix.n_rows(), co_count_matrix.n_cols()),
+            rhs: (tags.len(), tags.len()),
+        })
+    })?;
+
+    Ok(pair_npmi(PairCounts { c_ab, c_a, c_b, n_posts }))
+}
 
-    (p_ab.log2() - p_a.log2() - p_b.log2()) / -p_ab.log2()
+/// Index and count of `tag`.
+fn known_tag(tags: &Tags, tag: &str) -> Result<(usize, u32)> {
+    tags.get_idx(tag)
+        .and_then(|idx| Some((idx, tags.get_count_idx(idx)?)))
+        .ok_or_else(|| Error::UnknownTag(tag.to_string()))
+}
+
+/// Batched [`get_npmi`] that looks all pairs up in the co-count matrix in one
+/// pass. Pairs with a tag missing from `tags` come back as `None`.
+pub fn get_npmis(
+    tag_pairs: &[(&str, &str)],
+    n_posts: usize,
+    co_count_matrix: &CoCountMatrix,
+    tags: &Tags,
+) -> Result<Vec<Option<f32>>> {
+    let n_posts = PairCounts::checked_n_posts(n_posts)?;
+    let known: Vec<Option<(usize, usize)>> = tag_pairs
+        .iter()
+        .map(|(a, b)| Some((tags.get_idx(a)?, tags.get_idx(b)?)))
+        .collect();
+    let lookups: Vec<(usize, usize)> = known.iter().flatten().copied().collect();
+    let mut co_counts = co_count_matrix.values(&lookups).into_iter();
+
+    Ok(known
+        .into_iter()
+        .map(|pair| {
+            let (idx_a, idx_b) = pair?;
+            let c_ab = co_counts.next()??;
+            let c_a = tags.get_count_idx(idx_a)?;
+            let c_b = tags.get_count_idx(idx_b)?;
+            Some(pair_npmi(PairCounts { c_ab, c_a, c_b, n_posts }))
+        })
+        .collect())
+}
+
+/// [`Npmi`] score of `counts`, or -1 if the pair never co-occurs.
+fn pair_npmi(counts: PairCounts) -> f32 {
+    if counts.c_ab == 0 {
+        -1.0
+    } else {
+        Npmi.score(counts)
+    }
 }
 
 pub fn get_npmi_matrix(
     n_posts: usize, 
     tags: &Tags, 
-    co_count_matrix: &CSR
-) -> CSR {
-    let mut npmi_triples: Vec<(usize, usize, f32)> = Vec::with_capacity(co_count_matrix.n_nz);
-    let post_freq = 1.0 / n_posts as f32;
-    
-    let mut probabilities = Vec::with_capacity(tags.len());
-    for i in 0..tags.len() {
-        let p = tags.get_count_idx(i)
-            .unwrap()
-            as f32 * post_freq;
-        probabilities.push(p);
-    }
-    
-    for (row, col, val) in co_count_matrix {
-        let p_x = probabilities[row];
-        let p_y = probabilities[col];
-        let p_xy = val  * post_freq;
-        let npmi_xy = (p_xy / (p_x * p_y) ).log2() / -p_xy.log2();
-        if npmi_xy > 0.0 {
-            npmi_triples.push((row, col, npmi_xy));
-        }
+    co_count_matrix: &CoCountMatrix
+) -> Result<AssociationMatrix> {
+    get_association_matrix(n_posts, tags, co_count_matrix, &Npmi)
+}
+
+/// [`get_npmi_matrix`] with support thresholds and smoothing, so pairs of
+/// rare tags do not outrank real associations.
+pub fn get_smoothed_npmi_matrix(
+    n_posts: usize,
+    tags: &Tags,
+    co_count_matrix: &CoCountMatrix,
+    smoothing: Smoothing,
+) -> Result<AssociationMatrix> {
+    get_association_matrix(n_posts, tags, co_count_matrix, &SmoothedNpmi::new(tags, smoothing))
+}
+
+/// Scores every co-occurring pair of tags with `measure`. The result is an
+/// [`AssociationMatrix`] whatever the measure, so it can be pruned, saved
+/// and searched the same way; it is symmetric if the measure is. Fails if
+/// `n_posts` does not fit in the counts or `tags` does not match the
+/// co-count matrix.
+pub fn get_association_matrix<M: AssociationMeasure + ?Sized>(
+    n_posts: usize,
+    tags: &Tags,
+    co_count_matrix: &CoCountMatrix,
+    measure: &M,
+) -> Result<AssociationMatrix> {
+    let n_posts = PairCounts::checked_n_posts(n_posts)?;
+    if tags.len() != co_count_matrix.n_rows() {
+        return Err(Error::Matrix(csr_matrix::Error::DimensionMismatch {
+            lhs: (co_count_matrix.n_rows(), co_count_matrix.n_cols()),
+            rhs: (tags.len(), tags.len()),
+        }));
+    }
+    let counts: Vec<u32> = (0..tags.len())
+        .map(|i| tags.get_count_idx(i).unwrap())
+        .collect();
+    let score = |row: usize, col: usize, c_ab: u32| {
+        measure.score(PairCounts {
+            c_ab,
+            c_a: counts[row],
+            c_b: counts[col],
+            n_posts,
+        })
+    };
+
+    if measure.is_symmetric() {
+        Ok(AssociationMatrix::Symmetric(co_count_matrix.map_entries(score)))
+    } else {
+        Ok(AssociationMatrix::Full(co_count_matrix.to_csr().map_entries(score)))
+    }
+}
+
+/// Drops the entries of `association_matrix` below `params.min_score` and keeps
+/// the `params.top_k` best per row, so the matrix matches the params it is
+/// saved with. A symmetric matrix keeps a pair if it is among the best of
+/// either tag, so rows can keep more.
+pub fn prune_association_matrix(association_matrix: &AssociationMatrix, params: &BuildParams) -> AssociationMatrix {
+    let min = params.min_score.unwrap_or(f32::NEG_INFINITY);
+    let k = params.top_k.map(|k| k as usize);
+    match association_matrix {
+        AssociationMatrix::Symmetric(matrix) => AssociationMatrix::Symmetric(matrix.prune(min, k)),
+        AssociationMatrix::Full(matrix) => AssociationMatrix::Full(matrix.prune(min, k)),
     }
-    
-    CSR::from_triples(&npmi_triples, tags.len(), tags.len())
 }
 
+/// The `n_tags` tags most related to all of `tag_idxs` on average, only
+/// from `categories` if given. Tags of unknown category are left out unless
+/// `categories` has [`Category::Unknown`].
 pub fn get_most_related_tags(
     n_tags: usize,
-    npmi_matrix: &CSR,
-    tag_idxs: Vec<u32>
+    npmi_matrix: AssociationView<'_>,
+    tags: &Tags,
+    tag_idxs: Vec<u32>,
+    categories: Option<&[Category]>,
     ) -> Vec<(u32, f32)> {
-    let mut scores: Vec<f32> = vec![0.0; npmi_matrix.n_cols];
-    let mut counts: Vec<u32> = vec![0; npmi_matrix.n_cols];
+    let mut scores: Vec<f32> = vec![0.0; npmi_matrix.n_tags()];
+    let mut counts: Vec<u32> = vec![0; npmi_matrix.n_tags()];
 
     for tag_idx in &tag_idxs {
         for (rel_tag_idx, rel_score) in npmi_matrix.inter_row(*tag_idx as usize) {
@@ -104,15 +297,24 @@ pub fn get_most_related_tags(
         }
     }
 
-    for i in 0..npmi_matrix.n_cols {
+    for i in 0..npmi_matrix.n_tags() {
         if counts[i] != 0 {
         scores[i] /= counts[i] as f32;
         }
     }
 
+    let allowed: Option<Vec<bool>> = categories.map(|categories| {
+        let mut allowed = vec![false; npmi_matrix.n_tags()];
+        for idx in tags.with_categories(categories) {
+            allowed[idx] = true;
+        }
+        allowed
+    });
+
     scores.into_iter()
         .enumerate()
-        .filter(|(i ,s)| *s > 0.0f32)
+        .filter(|(_, s)| *s > 0.0f32)
+        .filter(|(i, _)| allowed.as_ref().is_none_or(|allowed| allowed.get(*i) == Some(&true)))
         .sorted_by(|a, b| {
             let s_a = a.1;
             let s_b = b.1;
@@ -123,3 +325,153 @@ pub fn get_most_related_tags(
         .take(n_tags)
         .collect()
 }
+
+/// Neighbours kept per tag when comparing the neighbour distributions of
+/// tags in [`mine_aliases`].
+const SIGNATURE_LEN: usize = 64;
+/// Neighbours among the strongest of more tags than this, such as `1girl`,
+/// tell tags apart too poorly to be worth comparing on.
+const MAX_SHARED_NEIGHBOUR: usize = 1000;
+
+/// Thresholds for [`mine_implications`] and [`mine_aliases`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiningParams {
+    /// Posts an implied pair must share, and posts each tag of an alias pair
+    /// must be on.
+    pub min_support: u32,
+    /// Least P(consequent | antecedent) of an implication.
+    pub min_confidence: f32,
+    /// Least cosine similarity of the neighbour distributions of an alias
+    /// pair.
+    pub min_similarity: f32,
+}
+
+/// A proposed implication or alias from `antecedent` to `consequent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Suggestion {
+    pub antecedent: u32,
+    pub consequent: u32,
+    /// P(consequent | antecedent) of an implication, or the similarity of an
+    /// alias pair.
+    pub score: f32,
+    /// Posts tagged with both.
+    pub support: u32,
+}
+
+/// Proposes `a -> b` wherever P(b | a) is at least `min_confidence` over at
+/// least `min_support` shared posts, best first. Of two tags that would
+/// imply each other, only the rarer implies the other.
+pub fn mine_implications(
+    tags: &Tags,
+    co_count_matrix: &CoCountMatrix,
+    params: MiningParams,
+) -> Result<Vec<Suggestion>> {
+    let counts = tag_counts(tags, co_count_matrix)?;
+
+    let mut suggestions = Vec::new();
+    for (a, b, c_ab) in co_count_matrix.iter_upper() {
+        if a == b || c_ab < params.min_support {
+            continue;
+        }
+        let passes = |antecedent: usize| c_ab as f32 / counts[antecedent] as f32 >= params.min_confidence;
+        // when both directions pass, the rarer tag implies the other, so
+        // suggestions never form a cycle
+        let (antecedent, consequent) = match (passes(a), passes(b)) {
+            (true, true) if (counts[b], b) < (counts[a], a) => (b, a),
+            (true, _) => (a, b),
+            (false, true) => (b, a),
+            (false, false) => continue,
+        };
+        suggestions.push(Suggestion {
+            antecedent: antecedent as u32,
+            consequent: consequent as u32,
+            score: c_ab as f32 / counts[antecedent] as f32,
+            support: c_ab,
+        });
+    }
+    sort_suggestions(&mut suggestions);
+
+    Ok(suggestions)
+}
+
+/// Proposes pairs of tags on at least `min_support` posts each whose
+/// distributions P(neighbour | tag) have a cosine similarity of at least
+/// `min_similarity`, best first. Each tag is compared on its strongest
+/// neighbours only. The rarer tag of a pair is the antecedent.
+pub fn mine_aliases(
+    tags: &Tags,
+    co_count_matrix: &CoCountMatrix,
+    params: MiningParams,
+) -> Result<Vec<Suggestion>> {
+    let counts = tag_counts(tags, co_count_matrix)?;
+    let n_tags = counts.len();
+
+    let signatures: CSR<f32, u32> = co_count_matrix
+        .to_csr()
+        .map_entries(|row, col, c| {
+            if row == col || counts[row] < params.min_support {
+                0.0
+            } else {
+                c as f32 / counts[row] as f32
+            }
+        })
+        .top_k_per_row(SIGNATURE_LEN);
+    let mut shared = vec![0; n_tags];
+    for (_, col, _) in signatures.iter() {
+        shared[col] += 1;
+    }
+    let signatures = signatures.map_entries(|_, col, p| if shared[col] > MAX_SHARED_NEIGHBOUR { 0.0 } else { p });
+    let norms: Vec<f32> = (0..n_tags)
+        .map(|row| signatures.inter_row(row).map(|(_, p)| p * p).sum::<f32>().sqrt())
+        .collect();
+    let signatures = signatures.map_entries(|row, _, p| p / norms[row]);
+    let by_neighbour = CSC::from_csr(&signatures);
+
+    let mut suggestions = Vec::new();
+    let mut dots = vec![0.0f32; n_tags];
+    let mut touched = Vec::new();
+    for a in 0..n_tags {
+        for (neighbour, p_a) in signatures.inter_row(a) {
+            for (b, p_b) in by_neighbour.inter_col(neighbour) {
+                if b > a {
+                    if dots[b] == 0.0 {
+                        touched.push(b);
+                    }
+                    dots[b] += p_a * p_b;
+                }
+            }
+        }
+        for b in touched.drain(..) {
+            let similarity = std::mem::take(&mut dots[b]).min(1.0);
+            if similarity >= params.min_similarity {
+                let (antecedent, consequent) = if counts[a] <= counts[b] { (a, b) } else { (b, a) };
+                suggestions.push(Suggestion {
+                    antecedent: antecedent as u32,
+                    consequent: consequent as u32,
+                    score: similarity,
+                    support: co_count_matrix.value(a, b).unwrap_or(0),
+                });
+            }
+        }
+    }
+    sort_suggestions(&mut suggestions);
+
+    Ok(suggestions)
+}
+
+/// Post count of every tag, checking the matrix covers the vocabulary.
+fn tag_counts(tags: &Tags, co_count_matrix: &CoCountMatrix) -> Result<Vec<u32>> {
+    if co_count_matrix.n_rows() != tags.len() {
+        return Err(Error::Matrix(csr_matrix::Error::DimensionMismatch {
+            lhs: (co_count_matrix.n_rows(), co_count_matrix.n_cols()),
+            rhs: (tags.len(), tags.len()),
+        }));
+    }
+
+    Ok((0..tags.len()).map(|idx| tags.get_count_idx(idx).expect("index is in the vocabulary")).collect())
+}
+
+/// Best score first, then most support.
+fn sort_suggestions(suggestions: &mut [Suggestion]) {
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.support.cmp(&a.support)));
+}
@@ -1,67 +1,718 @@
-use crate::tags::Tags;
-use crate::*;
+use crate::error::{Error, Result};
+use crate::nmpi::{AssociationMatrix, AssociationView, CoCountMatrix, Suggestion};
+use crate::tags::{Aliases, Category, Implications, LegacyTags, TagCategories, Tags};
+use crate::association::{Measure, Smoothing};
 
-use bincode;
-use bincode::config::Config;
+use csv::{ReaderBuilder, Writer};
+use csr_matrix::{CSRView, SymmetricCSRView, CSR};
+use memmap2::Mmap;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
 
-#[derive(Serialize, Deserialize)]
-struct SerializedData {
+pub type PostsTagIdxs = Vec<Vec<u32>>;
+
+const DATA_MAGIC: &[u8; 8] = b"PGNPMI\0\0";
+/// Bumped whenever the header or payload encoding changes. Shared by
+/// [`write_data`] and [`write_model`] files, which encode their tags the
+/// same way. Files from before the format was versioned are rewritten by
+/// [`migrate_data`].
+pub const DATA_VERSION: u32 = 1;
+
+/// How the matrix of a model was built.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BuildParams {
+    pub measure: Measure,
+    /// Smoothing of [`Measure::Npmi`] scores, see
+    /// [`crate::nmpi::get_smoothed_npmi_matrix`].
+    pub smoothing: Smoothing,
+    /// Entries below this score were pruned.
+    pub min_score: Option<f32>,
+    /// At most this many entries were kept per row.
+    pub top_k: Option<u32>,
+}
+
+/// Describes the payload of a file written by [`write_data`]. It follows the
+/// magic bytes, the format version and its own length, and is checked
+/// against the payload by [`read_data`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelHeader {
+    pub params: BuildParams,
+    pub n_posts: u64,
+    pub n_tags: u64,
+    /// Scores stored, once per pair if `symmetric`.
+    pub n_nz: u64,
+    /// Whether the matrix is an [`AssociationMatrix::Symmetric`].
+    pub symmetric: bool,
+    /// CRC-32 of the encoded tags, which in a [`write_model`] file run up to
+    /// the matrix. Checked when a model is opened.
+    pub tags_checksum: u32,
+    /// CRC-32 of the payload.
+    pub checksum: u32,
+}
+
+impl ModelHeader {
+    fn decode(bytes: &[u8]) -> std::result::Result<ModelHeader, ModelError> {
+        let (header, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .map_err(|e| ModelError::Corrupt(e.to_string()))?;
+        Ok(header)
+    }
+
+    /// Whether the header agrees with the tags and matrix it describes.
+    fn describes(&self, tags: &Tags, matrix: AssociationView) -> bool {
+        let square = match matrix {
+            AssociationView::Symmetric(_) => true,
+            AssociationView::Full(matrix) => matrix.n_cols == matrix.n_rows,
+        };
+        self.n_tags == tags.len() as u64
+            && self.n_nz == matrix.n_stored() as u64
+            && self.symmetric == matches!(matrix, AssociationView::Symmetric(_))
+            && matrix.n_tags() == tags.len()
+            && square
+    }
+}
+
+/// What `write_data` wrote before the format was versioned.
+#[derive(Deserialize)]
+struct LegacyData {
     n_posts: usize,
-    tags: Box<Tags>,
-    csr: Box<CSR>,
+    tags: LegacyTags,
+    csr: CSR<f32, u32>,
+}
+
+/// Why a model file could not be read.
+#[derive(Debug)]
+pub enum ModelError {
+    /// The file does not start with the model magic bytes.
+    NotAModel,
+    /// The file predates the versioned format, see [`migrate_data`].
+    Legacy,
+    UnsupportedVersion(u32),
+    ChecksumMismatch { expected: u32, found: u32 },
+    Corrupt(String),
+}
+
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::NotAModel => write!(f, "Not a model file"),
+            ModelError::Legacy => write!(f, "Model file predates the versioned format and must be migrated"),
+            ModelError::UnsupportedVersion(v) => {
+                write!(f, "Model format version {v} is not supported (newest is {DATA_VERSION})")
+            }
+            ModelError::ChecksumMismatch { expected, found } => {
+                write!(f, "Model checksum mismatch: header says {expected:#010x}, payload is {found:#010x}")
+            }
+            ModelError::Corrupt(reason) => write!(f, "Corrupt model: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// What [`read_posts_with`] does with tags implied by others in a post.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImpliedTags {
+    /// Leave posts as they are tagged.
+    #[default]
+    Keep,
+    /// Add every tag implied by one in the post, so posts tagged
+    /// inconsistently count the same.
+    Expand,
+    /// Drop every tag implied by another in the post, so a tag does not
+    /// co-occur with its own parents.
+    Strip,
 }
 
-pub fn read_posts(path: &str) -> Result<(usize, Tags, Vec<Vec<u32>>), Box<dyn Error>> {
+/// How [`read_posts_with`] ingests posts.
+#[derive(Debug, Clone, Default)]
+pub struct PostsOptions {
+    /// Applied to every tag, so an alias and its canonical tag are counted
+    /// as one. The vocabulary keeps the aliases for [`Tags::get_idx`].
+    pub aliases: Aliases,
+    /// Implications between tags, used as `implied` says. Both sides are
+    /// resolved through `aliases` first.
+    pub implications: Implications,
+    pub implied: ImpliedTags,
+    /// Categories of the canonical tags, see [`read_tag_categories`].
+    pub categories: TagCategories,
+}
+
+pub fn read_posts(path: &str) -> Result<(usize, Tags, PostsTagIdxs)> {
+    read_posts_with(path, &PostsOptions::default())
+}
+
+pub fn read_posts_with(path: &str, options: &PostsOptions) -> Result<(usize, Tags, PostsTagIdxs)> {
     use serde::Deserialize;
     #[derive(Debug, Deserialize)]
     struct Row {
         tag_string: String,
     }
 
+    let implications = options.implications.resolve_aliases(&options.aliases);
+    // aliasing can join two tags that imply each other
+    if let Some(tag) = implications.find_cycle() {
+        return Err(Error::ImplicationCycle(tag.to_string()));
+    }
+
     let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
 
     let mut tags = Tags::new();
 
     let mut n_posts = 0;
-    let mut posts_tag_idxs: Vec<Vec<u32>> = Vec::new();
+    let mut posts_tag_idxs: PostsTagIdxs = Vec::new();
     for line in reader.deserialize() {
         let row: Row = line?;
-        let tag_idxs: Vec<u32> = row
-            .tag_string
-            .split_whitespace()
-            .map(|tag| tags.add_or_increment(tag) as u32)
-            .collect();
+        let mut post: Vec<&str> = Vec::new();
+        for tag in row.tag_string.split_whitespace() {
+            let tag = options.aliases.resolve(tag);
+            // a post tagged with both an alias and its canonical tag
+            if !post.contains(&tag) {
+                post.push(tag);
+            }
+        }
+        match options.implied {
+            ImpliedTags::Keep => {}
+            ImpliedTags::Expand => {
+                for i in 0..post.len() {
+                    for implied in implications.implied(post[i]) {
+                        if !post.contains(&implied) {
+                            post.push(implied);
+                        }
+                    }
+                }
+            }
+            ImpliedTags::Strip => {
+                let implied: Vec<&str> = post.iter().flat_map(|tag| implications.implied(tag)).collect();
+                post.retain(|tag| !implied.contains(tag));
+            }
+        }
+        let tag_idxs: Vec<u32> = post.into_iter().map(|tag| tags.add_or_increment(tag) as u32).collect();
 
         posts_tag_idxs.push(tag_idxs);
 
         n_posts += 1;
     }
+    tags.set_aliases(&options.aliases);
+    tags.set_categories(&options.categories);
 
     Ok((n_posts, tags, posts_tag_idxs))
 }
 
-pub fn write_data(path: &str, n_posts: usize, tags: &Tags, npmi_matrix: &CSR) {
+/// Reads a tag alias CSV with `antecedent_name` and `consequent_name` (or
+/// `antecedent` and `consequent`) columns, such as a Danbooru
+/// `tag_aliases` export. Rows with a `status` other than `active`, or a
+/// `kind` other than `alias` as [`write_suggestions`] writes, are skipped.
+pub fn read_aliases(path: &str) -> Result<Aliases> {
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        #[serde(alias = "antecedent_name")]
+        antecedent: String,
+        #[serde(alias = "consequent_name")]
+        consequent: String,
+        status: Option<String>,
+        kind: Option<String>,
+    }
+
+    let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+
+    let mut aliases = Aliases::new();
+    for line in reader.deserialize() {
+        let row: Row = line?;
+        if row.status.is_some_and(|status| status != "active") || row.kind.is_some_and(|kind| kind != "alias") {
+            continue;
+        }
+        aliases.insert(&row.antecedent, &row.consequent);
+    }
+    if let Some(tag) = aliases.find_cycle() {
+        return Err(Error::AliasCycle(tag.to_string()));
+    }
+
+    Ok(aliases)
+}
+
+/// Reads a tag metadata CSV with `name` and `category` columns, such as a
+/// Danbooru `tags` export. Categories are names or Danbooru ids, see
+/// [`Category::parse`]; rows with any other category are skipped, leaving
+/// their tag [`Category::Unknown`].
+pub fn read_tag_categories(path: &str) -> Result<TagCategories> {
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        name: String,
+        category: String,
+    }
+
+    let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+
+    let mut categories = TagCategories::default();
+    let mut skipped = 0;
+    for line in reader.deserialize() {
+        let row: Row = line?;
+        match Category::parse(&row.category) {
+            Some(category) => {
+                categories.insert(row.name, category);
+            }
+            None => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        log::warn!("Skipped {skipped} tags of unknown category in {path}");
+    }
+
+    Ok(categories)
+}
+
+/// Reads a tag implication CSV with `antecedent_name` and `consequent_name`
+/// (or `antecedent` and `consequent`) columns, such as a Danbooru
+/// `tag_implications` export. Rows with a `status` other than `active`, or
+/// a `kind` other than `implication` as [`write_suggestions`] writes, are
+/// skipped.
+pub fn read_implications(path: &str) -> Result<Implications> {
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        #[serde(alias = "antecedent_name")]
+        antecedent: String,
+        #[serde(alias = "consequent_name")]
+        consequent: String,
+        status: Option<String>,
+        kind: Option<String>,
+    }
+
+    let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+
+    let mut implications = Implications::new();
+    for line in reader.deserialize() {
+        let row: Row = line?;
+        if row.status.is_some_and(|status| status != "active") || row.kind.is_some_and(|kind| kind != "implication") {
+            continue;
+        }
+        implications.insert(&row.antecedent, &row.consequent);
+    }
+    if let Some(tag) = implications.find_cycle() {
+        return Err(Error::ImplicationCycle(tag.to_string()));
+    }
+
+    Ok(implications)
+}
+
+pub fn write_data(
+    path: &str,
+    params: BuildParams,
+    n_posts: usize,
+    tags: &Tags,
+    npmi_matrix: &AssociationMatrix,
+) -> Result<()> {
     let cfg = bincode::config::standard();
-    let s = SerializedData {
-        n_posts,
-        tags: Box::new(tags.clone()),
-        csr: Box::new(npmi_matrix.clone()),
+    let encoded_tags = bincode::serde::encode_to_vec(tags, cfg).map_err(std::io::Error::other)?;
+    let encoded_matrix = bincode::serde::encode_to_vec(npmi_matrix, cfg).map_err(std::io::Error::other)?;
+    let payload = [encoded_tags.as_slice(), &encoded_matrix].concat();
+    let header = ModelHeader {
+        params,
+        n_posts: n_posts as u64,
+        n_tags: tags.len() as u64,
+        n_nz: npmi_matrix.n_stored() as u64,
+        symmetric: matches!(npmi_matrix, AssociationMatrix::Symmetric(_)),
+        tags_checksum: crc32fast::hash(&encoded_tags),
+        checksum: crc32fast::hash(&payload),
+    };
+    let header = bincode::serde::encode_to_vec(&header, cfg).map_err(std::io::Error::other)?;
+
+    write_atomically(path, |writer| {
+        writer.write_all(DATA_MAGIC)?;
+        writer.write_all(&DATA_VERSION.to_le_bytes())?;
+        writer.write_all(&(header.len() as u32).to_le_bytes())?;
+        writer.write_all(&header)?;
+        writer.write_all(&payload)?;
+        Ok(())
+    })
+}
+
+/// Reads and validates a file written by [`write_data`].
+pub fn read_data(path: &str) -> Result<(ModelHeader, Tags, AssociationMatrix)> {
+    let data = fs::read(path)?;
+    let cfg = bincode::config::standard();
+
+    if !data.starts_with(DATA_MAGIC) {
+        return match decode_legacy_data(&data) {
+            Ok(_) => Err(ModelError::Legacy.into()),
+            Err(_) => Err(ModelError::NotAModel.into()),
+        };
+    }
+
+    let truncated = || ModelError::Corrupt("truncated header".to_string());
+    let word = |at: usize| -> Result<u32> {
+        let bytes = data.get(at..at + 4).ok_or_else(truncated)?;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("slice is 4 bytes")))
     };
-    let encoded = bincode::serde::encode_to_vec(s, cfg).unwrap();
+    let version = word(8)?;
+    if version != DATA_VERSION {
+        return Err(ModelError::UnsupportedVersion(version).into());
+    }
+    let header_end = 16 + word(12)? as usize;
+    let header = ModelHeader::decode(data.get(16..header_end).ok_or_else(truncated)?)?;
+
+    let payload = &data[header_end..];
+    let found = crc32fast::hash(payload);
+    if found != header.checksum {
+        return Err(ModelError::ChecksumMismatch {
+            expected: header.checksum,
+            found,
+        }
+        .into());
+    }
+    let corrupt = |e: bincode::error::DecodeError| ModelError::Corrupt(e.to_string());
+    let (tags, tags_len): (Tags, _) = bincode::serde::decode_from_slice(payload, cfg).map_err(corrupt)?;
+    let found = crc32fast::hash(&payload[..tags_len]);
+    if found != header.tags_checksum {
+        return Err(ModelError::ChecksumMismatch {
+            expected: header.tags_checksum,
+            found,
+        }
+        .into());
+    }
+    let (npmi_matrix, _): (AssociationMatrix, _) =
+        bincode::serde::decode_from_slice(&payload[tags_len..], cfg).map_err(corrupt)?;
 
-    fs::write(path, encoded).unwrap();
+    if !header.describes(&tags, npmi_matrix.view()) {
+        return Err(ModelError::Corrupt("header does not match the payload".to_string()).into());
+    }
+
+    Ok((header, tags, npmi_matrix))
 }
 
-pub fn read_data(path: &str) -> Result<(usize, Tags, CSR), Box<dyn Error>> {
+/// Decodes what `write_data` wrote before the format was versioned.
+fn decode_legacy_data(data: &[u8]) -> std::result::Result<(usize, Tags, AssociationMatrix), String> {
+    let (legacy, _): (LegacyData, _) =
+        bincode::serde::decode_from_slice(data, bincode::config::standard()).map_err(|e| e.to_string())?;
+    Ok((legacy.n_posts, legacy.tags.into(), AssociationMatrix::Full(legacy.csr)))
+}
+
+/// Rewrites a file from before the format was versioned, replacing it
+/// atomically. Such files do not record how they were built, so `params`
+/// is used for them. Versioned files are only checked.
+pub fn migrate_data(path: &str, params: BuildParams) -> Result<()> {
     let data = fs::read(path)?;
+    if data.starts_with(DATA_MAGIC) {
+        read_data(path)?;
+        return Ok(());
+    }
+    let (n_posts, tags, npmi_matrix) = decode_legacy_data(&data).map_err(|_| ModelError::NotAModel)?;
+
+    write_data(path, params, n_posts, &tags, &npmi_matrix)
+}
+
+const MODEL_MAGIC: &[u8; 8] = b"PGMODEL\0";
+const ALIGN: usize = 64;
+
+/// Writes a model for [`MappedModel::open`]. Like a [`write_data`] file, it
+/// starts with magic bytes, the format version and a [`ModelHeader`] with
+/// its length, the header padded so the body starts 64-byte aligned. The
+/// body, which the checksum covers, is the length of the encoded tags as a
+/// u64, the tags, then the matrix in the aligned layout of
+/// [`CSRView::from_aligned`], also starting 64-byte aligned. A symmetric
+/// matrix is written as its upper triangle followed by its column index,
+/// see [`SymmetricCSRView::from_aligned`].
+pub fn write_model(
+    path: &str,
+    params: BuildParams,
+    n_posts: usize,
+    tags: &Tags,
+    npmi_matrix: &AssociationMatrix,
+) -> Result<()> {
     let cfg = bincode::config::standard();
-    let (ser, _): (SerializedData, _) = bincode::serde::decode_from_slice(&data, cfg)?;
+    let encoded_tags = bincode::serde::encode_to_vec(tags, cfg).map_err(std::io::Error::other)?;
+    let mut tags_section = (encoded_tags.len() as u64).to_le_bytes().to_vec();
+    tags_section.extend(&encoded_tags);
+    tags_section.resize(tags_section.len().next_multiple_of(ALIGN), 0);
+
+    // the matrix is hashed as it is written rather than buffered
+    let mut checksum = Crc32Writer(crc32fast::Hasher::new());
+    write_model_body(&mut checksum, &tags_section, npmi_matrix)?;
+    let header = ModelHeader {
+        params,
+        n_posts: n_posts as u64,
+        n_tags: tags.len() as u64,
+        n_nz: npmi_matrix.n_stored() as u64,
+        symmetric: matches!(npmi_matrix, AssociationMatrix::Symmetric(_)),
+        tags_checksum: crc32fast::hash(&tags_section),
+        checksum: checksum.0.finalize(),
+    };
+    let mut header = bincode::serde::encode_to_vec(&header, cfg).map_err(std::io::Error::other)?;
+    header.resize((16 + header.len()).next_multiple_of(ALIGN) - 16, 0);
+
+    write_atomically(path, |writer| {
+        writer.write_all(MODEL_MAGIC)?;
+        writer.write_all(&DATA_VERSION.to_le_bytes())?;
+        writer.write_all(&(header.len() as u32).to_le_bytes())?;
+        writer.write_all(&header)?;
+        write_model_body(writer, &tags_section, npmi_matrix)
+    })
+}
+
+fn write_model_body<W: Write>(mut writer: W, tags_section: &[u8], npmi_matrix: &AssociationMatrix) -> Result<()> {
+    writer.write_all(tags_section)?;
+    match npmi_matrix {
+        AssociationMatrix::Symmetric(matrix) => matrix.write_aligned(writer)?,
+        AssociationMatrix::Full(matrix) => matrix.write_aligned(writer)?,
+    }
+    Ok(())
+}
+
+/// Computes the CRC-32 of everything written to it.
+struct Crc32Writer(crc32fast::Hasher);
+
+impl Write for Crc32Writer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes a sibling temporary file, syncs it and renames it over `path`, so
+/// `path` is never seen half-written and an existing file at `path` is
+/// replaced rather than modified.
+fn write_atomically(path: &str, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let tmp_path = format!("{path}.tmp");
+    let written = File::create(&tmp_path).map_err(Error::from).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(fs::rename(&tmp_path, path)?)
+}
+
+/// A model memory-mapped from a file written by [`write_model`]. Opening it
+/// decodes the header and tags and checks them against the header, in time
+/// independent of the size of the matrix, which is read in place without
+/// being checked; see [`MappedModel::verify`].
+pub struct MappedModel {
+    mmap: Mmap,
+    header: ModelHeader,
+    tags: Tags,
+    body_at: usize,
+    matrix_at: usize,
+}
+
+impl MappedModel {
+    pub fn open(path: &str) -> Result<MappedModel> {
+        let mmap = map_model(path)?;
+
+        if !mmap.starts_with(MODEL_MAGIC) {
+            return Err(ModelError::NotAModel.into());
+        }
+        let corrupt = |reason: &str| ModelError::Corrupt(reason.to_string());
+        let word = |at: usize| -> Result<u32> {
+            let bytes = mmap.get(at..at + 4).ok_or_else(|| corrupt("truncated header"))?;
+            Ok(u32::from_le_bytes(bytes.try_into().expect("slice is 4 bytes")))
+        };
+        let version = word(8)?;
+        if version != DATA_VERSION {
+            return Err(ModelError::UnsupportedVersion(version).into());
+        }
+        let body_at = 16 + word(12)? as usize;
+        let header = ModelHeader::decode(mmap.get(16..body_at).ok_or_else(|| corrupt("truncated header"))?)?;
+        if !body_at.is_multiple_of(ALIGN) {
+            return Err(corrupt("misaligned body").into());
+        }
+
+        let body = &mmap[body_at..];
+        let (encoded_tags, matrix_at) = mapped_tags(body)?;
+        let found = crc32fast::hash(body.get(..matrix_at).ok_or_else(|| corrupt("truncated tags"))?);
+        if found != header.tags_checksum {
+            return Err(ModelError::ChecksumMismatch {
+                expected: header.tags_checksum,
+                found,
+            }
+            .into());
+        }
+        let (tags, read) = bincode::serde::decode_from_slice(encoded_tags, bincode::config::standard())
+            .map_err(|e| ModelError::Corrupt(e.to_string()))?;
+        if read != encoded_tags.len() {
+            return Err(corrupt("tags are shorter than their length").into());
+        }
+        let model = MappedModel {
+            mmap,
+            header,
+            tags,
+            body_at,
+            matrix_at: body_at + matrix_at,
+        };
+        let matrix = model.view().map_err(|e| corrupt(&e.to_string()))?;
+        if !model.header.describes(&model.tags, matrix) {
+            return Err(corrupt("header does not match the payload").into());
+        }
 
-    let n_posts = ser.n_posts;
-    let tags = *ser.tags;
-    let npmi_matrix = *ser.csr;
+        Ok(model)
+    }
+
+    /// Checks what opening the model does not, in O(file size): the
+    /// checksum of the whole body, and that the matrix and its index are
+    /// consistent, so lookups on it never panic. Models written by
+    /// [`write_model`] are consistent, so this is for files that may have
+    /// been damaged since.
+    pub fn verify(&self) -> Result<()> {
+        let found = crc32fast::hash(&self.mmap[self.body_at..]);
+        if found != self.header.checksum {
+            return Err(ModelError::ChecksumMismatch {
+                expected: self.header.checksum,
+                found,
+            }
+            .into());
+        }
+        let bytes = &self.mmap[self.matrix_at..];
+        let checked = if self.header.symmetric {
+            SymmetricCSRView::<f32, u32>::from_aligned(bytes).map(|_| ())
+        } else {
+            CSRView::<f32, u32>::from_aligned(bytes).map(|_| ())
+        };
+
+        Ok(checked.map_err(|e| ModelError::Corrupt(e.to_string()))?)
+    }
+
+    pub fn header(&self) -> &ModelHeader {
+        &self.header
+    }
+
+    pub fn n_posts(&self) -> usize {
+        self.header.n_posts as usize
+    }
+
+    pub fn tags(&self) -> &Tags {
+        &self.tags
+    }
+
+    pub fn npmi_matrix(&self) -> AssociationView<'_> {
+        self.view().expect("matrix was viewed on open")
+    }
+
+    /// Views the matrix checking only its headers and bounds, in O(1).
+    fn view(&self) -> std::result::Result<AssociationView<'_>, csr_matrix::Error> {
+        let bytes = self.mmap.get(self.matrix_at..).unwrap_or_default();
+        Ok(if self.header.symmetric {
+            AssociationView::Symmetric(SymmetricCSRView::from_aligned_trusted(bytes)?)
+        } else {
+            AssociationView::Full(CSRView::from_aligned_trusted(bytes)?)
+        })
+    }
+}
+
+fn map_model(path: &str) -> Result<Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the mapped file must not be modified while it is mapped.
+    // Models are never written to in place: `write_model` renames a new
+    // file over the old one, which leaves this mapping on the old file.
+    // Anything else writing to the file breaks this.
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+/// The encoded tags at the start of a model body, stored as a u64 length
+/// followed by that many bytes, with the 64-byte aligned offset that
+/// follows them.
+fn mapped_tags(body: &[u8]) -> Result<(&[u8], usize)> {
+    let corrupt = || ModelError::Corrupt("truncated tags".to_string());
+    let tags_len = body
+        .get(..8)
+        .map(|len| u64::from_le_bytes(len.try_into().expect("slice is 8 bytes")))
+        .ok_or_else(corrupt)?;
+    let tags_end = usize::try_from(tags_len)
+        .ok()
+        .and_then(|len| len.checked_add(8))
+        .ok_or_else(corrupt)?;
+    let encoded_tags = body.get(8..tags_end).ok_or_else(corrupt)?;
+    let matrix_at = tags_end.checked_next_multiple_of(ALIGN).ok_or_else(corrupt)?;
+
+    Ok((encoded_tags, matrix_at))
+}
+
+/// Writes the association matrix as a Matrix Market file, with the tag names
+/// next to it in a `.tags` file (line `i` names row and column `i`). A
+/// symmetric matrix is written as a `symmetric` file.
+pub fn write_association_mtx(path: &str, tags: &Tags, association_matrix: &AssociationMatrix) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    match association_matrix {
+        AssociationMatrix::Symmetric(matrix) => matrix.write_mtx(writer)?,
+        AssociationMatrix::Full(matrix) => matrix.write_mtx(writer)?,
+    }
+    write_tag_names(path, tags)
+}
+
+/// Writes the co-count matrix as a symmetric Matrix Market file, with the
+/// tag names next to it in a `.tags` file.
+pub fn write_co_count_mtx(
+    path: &str,
+    tags: &Tags,
+    co_count_matrix: &CoCountMatrix,
+) -> Result<()> {
+    co_count_matrix.write_mtx(BufWriter::new(File::create(path)?))?;
+    write_tag_names(path, tags)
+}
+
+/// Writes the association matrix for `scipy.sparse.load_npz`, with the tag
+/// names next to it in a `.tags` file.
+pub fn write_association_npz(path: &str, tags: &Tags, association_matrix: &AssociationMatrix) -> Result<()> {
+    association_matrix.to_csr().write_npz(BufWriter::new(File::create(path)?))?;
+    write_tag_names(path, tags)
+}
+
+/// Writes mined implications then mined aliases, each best first, as a CSV
+/// for review. Once reviewed, the file can be read back by
+/// [`read_implications`] and [`read_aliases`], which each keep their `kind`.
+pub fn write_suggestions(
+    path: &str,
+    tags: &Tags,
+    implications: &[Suggestion],
+    aliases: &[Suggestion],
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Row<'a> {
+        kind: &'a str,
+        antecedent: &'a str,
+        consequent: &'a str,
+        score: f32,
+        support: u32,
+        antecedent_count: u32,
+        consequent_count: u32,
+    }
+
+    let name = |idx: u32| {
+        tags.get_name(idx as usize)
+            .ok_or_else(|| Error::UnknownTag(format!("#{idx}")))
+    };
+    let mut writer = Writer::from_path(path)?;
+    let kinds = implications.iter().map(|s| ("implication", s)).chain(aliases.iter().map(|s| ("alias", s)));
+    for (kind, suggestion) in kinds {
+        writer.serialize(Row {
+            kind,
+            antecedent: name(suggestion.antecedent)?,
+            consequent: name(suggestion.consequent)?,
+            score: suggestion.score,
+            support: suggestion.support,
+            antecedent_count: tags.get_count_idx(suggestion.antecedent as usize).unwrap_or(0),
+            consequent_count: tags.get_count_idx(suggestion.consequent as usize).unwrap_or(0),
+        })?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_tag_names(path: &str, tags: &Tags) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(Path::new(path).with_extension("tags"))?);
+    for name in (0..tags.len()).filter_map(|idx| tags.get_name(idx)) {
+        writeln!(writer, "{name}")?;
+    }
+    writer.flush()?;
 
-    Ok((n_posts, tags, npmi_matrix))
+    Ok(())
 }
@@ -1,19 +1,184 @@
-use rustc_hash::FxHashMap;
-use serde::ser::SerializeStruct;
+use csr_matrix::IndexMap;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::de::Error as _;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone, Debug)]
 struct TagData {
-    name: Rc<str>,
+    name: Arc<str>,
     count: u32,
+    category: Category,
 }
 
+/// Kind of thing a tag names, as categorised by Danbooru.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Category {
+    General,
+    Artist,
+    Copyright,
+    Character,
+    Meta,
+    /// The tag is not in the tag metadata, so it only matches a category
+    /// filter that asks for unknown tags.
+    #[default]
+    Unknown,
+}
+
+impl Category {
+    /// Parses a category name, or a Danbooru category id (`0` general, `1`
+    /// artist, `3` copyright, `4` character, `5` meta).
+    pub fn parse(category: &str) -> Option<Category> {
+        match category.trim().to_ascii_lowercase().as_str() {
+            "0" | "general" => Some(Category::General),
+            "1" | "artist" => Some(Category::Artist),
+            "3" | "copyright" => Some(Category::Copyright),
+            "4" | "character" => Some(Category::Character),
+            "5" | "meta" => Some(Category::Meta),
+            _ => None,
+        }
+    }
+}
+
+/// Category of each tag by name, see [`Tags::set_categories`].
+pub type TagCategories = FxHashMap<String, Category>;
+
+/// Maps alias tags to the canonical tag they stand for, e.g. `4_legged` to
+/// `quadruped`.
+#[derive(Debug, Clone, Default)]
+pub struct Aliases {
+    map: FxHashMap<String, String>,
+}
+
+impl Aliases {
+    pub fn new() -> Aliases {
+        Aliases::default()
+    }
+
+    pub fn insert(&mut self, antecedent: &str, consequent: &str) {
+        self.map.insert(antecedent.to_string(), consequent.to_string());
+    }
+
+    /// The canonical name of `tag`, following chains of aliases. `tag`
+    /// itself if it is not an alias.
+    pub fn resolve<'a>(&'a self, tag: &'a str) -> &'a str {
+        let mut name = tag;
+        // bounded so a cycle cannot loop forever
+        for _ in 0..self.map.len() {
+            match self.map.get(name) {
+                Some(next) => name = next,
+                None => break,
+            }
+        }
+        name
+    }
+
+    /// An alias whose chain never reaches a canonical tag, if any.
+    pub fn find_cycle(&self) -> Option<&str> {
+        self.map
+            .keys()
+            .find(|tag| self.map.contains_key(self.resolve(tag)))
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Implications between tags, e.g. `cat` implies `feline`, which implies
+/// `mammal`.
+#[derive(Debug, Clone, Default)]
+pub struct Implications {
+    /// Tags each tag directly implies.
+    map: FxHashMap<String, Vec<String>>,
+}
+
+impl Implications {
+    pub fn new() -> Implications {
+        Implications::default()
+    }
+
+    pub fn insert(&mut self, antecedent: &str, consequent: &str) {
+        let consequents = self.map.entry(antecedent.to_string()).or_default();
+        if !consequents.iter().any(|c| c == consequent) {
+            consequents.push(consequent.to_string());
+        }
+    }
+
+    /// Every tag `tag` implies, directly or through other tags, nearest
+    /// first. Includes `tag` itself only if it is part of a cycle.
+    pub fn implied<'a>(&'a self, tag: &str) -> Vec<&'a str> {
+        let mut implied: Vec<&str> = Vec::new();
+        let mut seen: FxHashSet<&str> = FxHashSet::default();
+        let mut next = 0;
+        let mut consequents = self.map.get(tag);
+        loop {
+            for consequent in consequents.into_iter().flatten() {
+                if seen.insert(consequent) {
+                    implied.push(consequent);
+                }
+            }
+            let Some(&tag) = implied.get(next) else {
+                break;
+            };
+            consequents = self.map.get(tag);
+            next += 1;
+        }
+        implied
+    }
+
+    /// These implications between the canonical tags of `aliases`, dropping
+    /// those of a tag on itself.
+    pub fn resolve_aliases(&self, aliases: &Aliases) -> Implications {
+        let mut resolved = Implications::new();
+        for (antecedent, consequents) in &self.map {
+            let antecedent = aliases.resolve(antecedent);
+            for consequent in consequents {
+                let consequent = aliases.resolve(consequent);
+                if antecedent != consequent {
+                    resolved.insert(antecedent, consequent);
+                }
+            }
+        }
+        resolved
+    }
+
+    /// Whether `antecedent` implies `consequent`, directly or not.
+    pub fn implies(&self, antecedent: &str, consequent: &str) -> bool {
+        self.implied(antecedent).contains(&consequent)
+    }
+
+    /// A tag that implies itself through a chain of implications, if any.
+    pub fn find_cycle(&self) -> Option<&str> {
+        self.map
+            .keys()
+            .find(|tag| self.implies(tag, tag))
+            .map(String::as_str)
+    }
+
+    /// Number of direct implications.
+    pub fn len(&self) -> usize {
+        self.map.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Tag vocabulary: names, post counts and the index of every tag. Names are
+/// shared through `Arc`, so a `Tags` can be read from many threads at once.
 #[derive(Debug, Clone)]
 pub struct Tags {
-    tag_set: FxHashMap<Rc<str>, usize>,
+    tag_set: FxHashMap<Arc<str>, usize>,
     vec: Vec<TagData>,
+    /// Index of the canonical tag of every alias, see [`Tags::set_aliases`].
+    aliases: FxHashMap<Arc<str>, usize>,
 }
 
 impl Tags {
@@ -21,6 +186,7 @@ impl Tags {
         Tags {
             tag_set: FxHashMap::default(),
             vec: Vec::new(),
+            aliases: FxHashMap::default(),
         }
     }
 
@@ -29,26 +195,61 @@ impl Tags {
             self.vec[*idx].count += 1;
             idx.to_owned()
         } else {
-            let tag_name: Rc<str> = Rc::from(tag);
+            let tag_name: Arc<str> = Arc::from(tag);
             let tag_data = TagData {
-                name: Rc::clone(&tag_name),
-                count: 1
+                name: Arc::clone(&tag_name),
+                count: 1,
+                category: Category::Unknown,
             };
             self.vec.push(tag_data);
             let idx = self.vec.len() -1;
-            self.tag_set.insert(Rc::clone(&tag_name), idx);
+            self.tag_set.insert(Arc::clone(&tag_name), idx);
             idx
         }
     }
 
+    /// Index of `tag`, or of its canonical tag if it is an alias.
     pub fn get_idx(&self, tag: &str) -> Option<usize> {
-        if self.tag_set.contains_key(tag) {
-            Some(self.tag_set.get(tag).unwrap().to_owned())
-        } else {
-            None
+        self.tag_set.get(tag).or_else(|| self.aliases.get(tag)).copied()
+    }
+
+    /// Makes [`Tags::get_idx`] resolve `aliases`. Aliases whose canonical tag
+    /// is not in the vocabulary are dropped.
+    pub fn set_aliases(&mut self, aliases: &Aliases) {
+        self.aliases = aliases
+            .map
+            .keys()
+            .filter(|antecedent| !self.tag_set.contains_key(antecedent.as_str()))
+            .filter_map(|antecedent| {
+                let idx = *self.tag_set.get(aliases.resolve(antecedent))?;
+                Some((Arc::from(antecedent.as_str()), idx))
+            })
+            .collect();
+    }
+
+    pub fn n_aliases(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// Sets the category of every tag in `categories`; tags not in it are
+    /// [`Category::Unknown`].
+    pub fn set_categories(&mut self, categories: &TagCategories) {
+        for tag_data in &mut self.vec {
+            tag_data.category = categories.get(&*tag_data.name).copied().unwrap_or_default();
         }
     }
 
+    pub fn get_category(&self, idx: usize) -> Option<Category> {
+        self.vec.get(idx).map(|tag_data| tag_data.category)
+    }
+
+    /// Indices of the tags in any of `categories`, in index order.
+    pub fn with_categories(&self, categories: &[Category]) -> Vec<usize> {
+        (0..self.vec.len())
+            .filter(|&idx| categories.contains(&self.vec[idx].category))
+            .collect()
+    }
+
     pub fn get_name(&self, idx: usize) -> Option<&str> {
         if idx < self.vec.len() {
             Some(&self.vec[idx].name)
@@ -58,12 +259,7 @@ impl Tags {
     }
 
     pub fn get_count(&self, name: &str) -> Option<u32> {
-        if self.tag_set.contains_key(name) {
-            let idx = self.tag_set[name];
-            Some(self.vec[idx].count)
-        } else {
-            None
-        }
+        self.get_idx(name).map(|idx| self.vec[idx].count)
     }
 
     pub fn get_count_idx(&self, idx: usize) -> Option<u32> {
@@ -77,25 +273,56 @@ impl Tags {
     pub fn len(&self) -> usize {
         self.vec.len()
     }
-}
 
-impl Serialize for TagData {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut ser_tag_data =
-            serializer.serialize_struct("TagData", 2)?;
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Indices of the `n` most frequent tags, most frequent first.
+    pub fn most_frequent(&self, n: usize) -> Vec<usize> {
+        let mut idxs: Vec<usize> = (0..self.vec.len()).collect();
+        idxs.sort_by_key(|&i| std::cmp::Reverse(self.vec[i].count));
+        idxs.truncate(n);
+        idxs
+    }
 
-        let name = &*self.name;
-        let count = self.count;
+    /// Builds a vocabulary where new tag `i` is old tag `idxs[i]`, matching
+    /// `CSR::submatrix(idxs, idxs)`.
+    pub fn select(&self, idxs: &[usize]) -> Tags {
+        let mut tags = Tags::new();
+        let mut new_idxs = vec![None; self.vec.len()];
+        for &idx in idxs {
+            let tag_data = self.vec[idx].clone();
+            new_idxs[idx].get_or_insert(tags.vec.len());
+            tags.tag_set.insert(Arc::clone(&tag_data.name), tags.vec.len());
+            tags.vec.push(tag_data);
+        }
+        tags.aliases = self
+            .aliases
+            .iter()
+            .filter_map(|(antecedent, &idx)| Some((Arc::clone(antecedent), new_idxs[idx]?)))
+            .collect();
+        tags
+    }
 
-        ser_tag_data.serialize_field("name", name)?;
-        ser_tag_data.serialize_field("count", &count)?;
-        ser_tag_data.end()
+    /// Applies an old-to-new index map such as the one returned by
+    /// `CSR::compact_square`, dropping tags that map to `None`.
+    pub fn remap(&self, map: &IndexMap) -> Tags {
+        let mut idxs = vec![0; map.iter().flatten().count()];
+        for (old, new) in map.iter().enumerate() {
+            if let Some(new) = new {
+                idxs[*new] = old;
+            }
+        }
+        self.select(&idxs)
     }
 }
 
+impl Default for Tags {
+    fn default() -> Self {
+        Tags::new()
+    }
+}
 
 impl<'de> Deserialize<'de> for TagData {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -109,17 +336,168 @@ impl<'de> Deserialize<'de> for TagData {
         }
 
         let helper = Helper::deserialize(deserializer)?;
-        let name: Rc<str> = Rc::from(helper.name.as_str().to_owned());
+        let name: Arc<str> = Arc::from(helper.name.as_str().to_owned());
         let count: u32 = helper.count;
 
-        Ok(TagData { name, count })
+        Ok(TagData {
+            name,
+            count,
+            category: Category::Unknown,
+        })
     }
 }
 
+/// How [`Tags`] were serialized before model files were versioned: every
+/// tag with its count, followed by the name map.
 #[derive(Deserialize)]
-struct SerializedTagsHelper {
+pub(crate) struct LegacyTags {
     vec: Vec<TagData>,
-    tag_set: FxHashMap<String, usize>
+    #[serde(rename = "tag_set")]
+    _tag_set: FxHashMap<String, usize>,
+}
+
+impl From<LegacyTags> for Tags {
+    fn from(legacy: LegacyTags) -> Tags {
+        let tag_set = legacy
+            .vec
+            .iter()
+            .enumerate()
+            .map(|(idx, tag_data)| (Arc::clone(&tag_data.name), idx))
+            .collect();
+
+        Tags {
+            tag_set,
+            vec: legacy.vec,
+            aliases: FxHashMap::default(),
+        }
+    }
+}
+
+/// Front-coded list of strings: sorted, with each storing only what it does
+/// not share with the one before it, so tags sharing a prefix such as
+/// `artist:` store it once.
+#[derive(Serialize, Deserialize)]
+struct StringTable {
+    /// Position in the list of each string, in sorted order.
+    order: Vec<u32>,
+    /// Bytes each sorted string shares with the one before it.
+    shared: Vec<u32>,
+    /// Remainder of each sorted string, concatenated.
+    suffixes: String,
+    suffix_lens: Vec<u32>,
+}
+
+impl StringTable {
+    fn encode(strings: &[&str]) -> StringTable {
+        let mut order: Vec<u32> = (0..strings.len() as u32).collect();
+        order.sort_unstable_by_key(|&i| strings[i as usize]);
+
+        let mut shared = Vec::with_capacity(order.len());
+        let mut suffixes = String::new();
+        let mut suffix_lens = Vec::with_capacity(order.len());
+        let mut prev: &str = "";
+        for &i in &order {
+            let string = strings[i as usize];
+            let mut n = prev.bytes().zip(string.bytes()).take_while(|(a, b)| a == b).count();
+            while !string.is_char_boundary(n) {
+                n -= 1;
+            }
+            shared.push(n as u32);
+            suffixes.push_str(&string[n..]);
+            suffix_lens.push((string.len() - n) as u32);
+            prev = string;
+        }
+
+        StringTable {
+            order,
+            shared,
+            suffixes,
+            suffix_lens,
+        }
+    }
+
+    fn decode(self) -> Result<Vec<Arc<str>>, &'static str> {
+        let n = self.order.len();
+        if self.shared.len() != n || self.suffix_lens.len() != n {
+            return Err("string table columns differ in length");
+        }
+
+        let mut strings: Vec<Option<Arc<str>>> = vec![None; n];
+        let mut string = String::new();
+        let mut at = 0;
+        for i in 0..n {
+            let shared = self.shared[i] as usize;
+            let suffix = self
+                .suffixes
+                .get(at..at + self.suffix_lens[i] as usize)
+                .ok_or("string suffix out of bounds")?;
+            at += suffix.len();
+            if shared > string.len() || !string.is_char_boundary(shared) {
+                return Err("string prefix out of bounds");
+            }
+            string.truncate(shared);
+            string.push_str(suffix);
+
+            match strings.get_mut(self.order[i] as usize) {
+                Some(slot @ None) => *slot = Some(Arc::from(string.as_str())),
+                _ => return Err("string order is not a permutation"),
+            }
+        }
+
+        Ok(strings.into_iter().map(|s| s.expect("order is a permutation")).collect())
+    }
+}
+
+/// Serialized form of [`Tags`]; the name map is rebuilt on load.
+#[derive(Serialize, Deserialize)]
+struct SerializedTags {
+    names: StringTable,
+    /// Post count of each tag, by tag index.
+    counts: Vec<u32>,
+    alias_names: StringTable,
+    /// Canonical tag index of each alias.
+    alias_idxs: Vec<u32>,
+    /// Category of each tag, by tag index.
+    categories: Vec<Category>,
+}
+
+impl Tags {
+    fn from_parts(
+        names: StringTable,
+        counts: Vec<u32>,
+        alias_names: Vec<Arc<str>>,
+        alias_idxs: Vec<u32>,
+        categories: Vec<Category>,
+    ) -> Result<Tags, &'static str> {
+        let names = names.decode()?;
+        if names.len() != counts.len() || names.len() != categories.len() || alias_names.len() != alias_idxs.len() {
+            return Err("tag table columns differ in length");
+        }
+
+        let vec: Vec<TagData> = names
+            .into_iter()
+            .zip(counts)
+            .zip(categories)
+            .map(|((name, count), category)| TagData { name, count, category })
+            .collect();
+        let tag_set: FxHashMap<Arc<str>, usize> = vec
+            .iter()
+            .enumerate()
+            .map(|(idx, tag_data)| (Arc::clone(&tag_data.name), idx))
+            .collect();
+        if tag_set.len() != vec.len() {
+            return Err("duplicate tag names");
+        }
+        if alias_idxs.iter().any(|&idx| idx as usize >= vec.len()) {
+            return Err("alias of an unknown tag");
+        }
+
+        Ok(Tags {
+            tag_set,
+            vec,
+            aliases: alias_names.into_iter().zip(alias_idxs.into_iter().map(|idx| idx as usize)).collect(),
+        })
+    }
 }
 
 impl Serialize for Tags {
@@ -127,21 +505,21 @@ impl Serialize for Tags {
     where
         S: Serializer,
     {
-        let mut ser_tags =
-            serializer.serialize_struct("Tags", 2)?;
+        let names: Vec<&str> = self.vec.iter().map(|tag_data| &*tag_data.name).collect();
+        let (alias_names, alias_idxs): (Vec<&str>, Vec<u32>) = self
+            .aliases
+            .iter()
+            .map(|(antecedent, &idx)| (&**antecedent, idx as u32))
+            .unzip();
 
-        let ser_helper = SerializedTagsHelper {
-            vec: self.vec.iter()
-                .map(|td| td.clone())
-                .collect(),
-            tag_set: self.tag_set.iter()
-              .map(|(k, v)| (String::from(&**k), *v))
-              .collect(),
-        };
-
-        ser_tags.serialize_field("vec", &ser_helper.vec)?;
-        ser_tags.serialize_field("tag_set", &ser_helper.tag_set)?;
-        ser_tags.end()
+        SerializedTags {
+            names: StringTable::encode(&names),
+            counts: self.vec.iter().map(|tag_data| tag_data.count).collect(),
+            alias_names: StringTable::encode(&alias_names),
+            alias_idxs,
+            categories: self.vec.iter().map(|tag_data| tag_data.category).collect(),
+        }
+        .serialize(serializer)
     }
 }
 
@@ -150,16 +528,8 @@ impl<'de> Deserialize<'de> for Tags {
     where
         D: Deserializer<'de>,
     {
-        let helper = SerializedTagsHelper::deserialize(deserializer)?;
-        let mut tag_set: FxHashMap<Rc<str>, usize> = FxHashMap::default();
-        for (idx, tag) in helper.vec.iter().enumerate() {
-            let tag: Rc<str> = Rc::clone(&helper.vec[idx].name);
-            tag_set.insert(Rc::clone(&tag), idx);
-        }
-
-        Ok(Tags {
-            tag_set,
-            vec: helper.vec
-        })
+        let ser = SerializedTags::deserialize(deserializer)?;
+        let alias_names = ser.alias_names.decode().map_err(D::Error::custom)?;
+        Tags::from_parts(ser.names, ser.counts, alias_names, ser.alias_idxs, ser.categories).map_err(D::Error::custom)
     }
 }
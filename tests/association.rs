@@ -0,0 +1,282 @@
+//! Association measures on hand-computed contingency tables.
+
+use csr_matrix::SymmetricCSR;
+use prompt_get::association::{
+    AssociationMeasure, ChiSquare, ConditionalProbability, Dice, Jaccard, Lift, LogLikelihoodRatio, Measure, Npmi,
+    PairCounts, Pmi, Ppmi, SmoothedNpmi, Smoothing,
+};
+use prompt_get::error::Error;
+use prompt_get::nmpi::{
+    get_association_matrix, get_co_count_matrix, get_npmi, get_npmi_matrix, get_npmis, get_smoothed_npmi_matrix,
+    CoCountMatrix,
+};
+use prompt_get::tags::Tags;
+
+/// `a` on 20 of 100 posts, `b` on 50, together on 10: exactly as often as
+/// chance.
+const INDEPENDENT: PairCounts = PairCounts {
+    c_ab: 10,
+    c_a: 20,
+    c_b: 50,
+    n_posts: 100,
+};
+
+/// The table `[[40, 10], [20, 30]]`, whose expected counts under
+/// independence are `[[30, 20], [30, 20]]`.
+const ATTRACTED: PairCounts = PairCounts {
+    c_ab: 40,
+    c_a: 50,
+    c_b: 60,
+    n_posts: 100,
+};
+
+fn swapped(counts: PairCounts) -> PairCounts {
+    PairCounts {
+        c_a: counts.c_b,
+        c_b: counts.c_a,
+        ..counts
+    }
+}
+
+fn assert_close(found: f32, expected: f64) {
+    assert!((found as f64 - expected).abs() < 1e-4, "{found} is not {expected}");
+}
+
+#[test]
+fn independent_tags_are_not_associated() {
+    assert_close(Pmi.score(INDEPENDENT), 0.0);
+    assert_close(Ppmi.score(INDEPENDENT), 0.0);
+    assert_close(Npmi.score(INDEPENDENT), 0.0);
+    assert_close(Lift.score(INDEPENDENT), 1.0);
+    assert_close(LogLikelihoodRatio.score(INDEPENDENT), 0.0);
+    assert_close(ChiSquare.score(INDEPENDENT), 0.0);
+}
+
+#[test]
+fn test_statistics_match_a_textbook_table() {
+    // 100 (40 * 30 - 10 * 20)² / (50 * 50 * 60 * 40)
+    assert_close(ChiSquare.score(ATTRACTED), 50.0 / 3.0);
+    // 2 Σ O ln(O / E)
+    let g2 = 2.0
+        * (40.0 * (40.0f64 / 30.0).ln()
+            + 10.0 * (10.0f64 / 20.0).ln()
+            + 20.0 * (20.0f64 / 30.0).ln()
+            + 30.0 * (30.0f64 / 20.0).ln());
+    assert_close(LogLikelihoodRatio.score(ATTRACTED), g2);
+    assert_close(LogLikelihoodRatio.score(ATTRACTED), 17.260924);
+
+    // the same table with the tags swapped
+    assert_close(ChiSquare.score(swapped(ATTRACTED)), 50.0 / 3.0);
+    // the table `[[10, 40], [50, 0]]` co-occurs less than chance
+    let repelled = PairCounts {
+        c_ab: 10,
+        ..ATTRACTED
+    };
+    assert_eq!(ChiSquare.score(repelled), 0.0);
+    assert_eq!(LogLikelihoodRatio.score(repelled), 0.0);
+}
+
+#[test]
+fn overlap_measures_match_known_counts() {
+    assert_close(Jaccard.score(ATTRACTED), 40.0 / 70.0);
+    assert_close(Dice.score(ATTRACTED), 80.0 / 110.0);
+    assert_close(Lift.score(ATTRACTED), 40.0 * 100.0 / (50.0 * 60.0));
+    assert_close(Pmi.score(ATTRACTED), (4.0f64 / 3.0).log2());
+    assert_close(Npmi.score(ATTRACTED), (4.0f64 / 3.0).log2() / -(0.4f64).log2());
+}
+
+fn tags(counts: &[(&str, u32)]) -> Tags {
+    let mut tags = Tags::new();
+    for &(tag, count) in counts {
+        for _ in 0..count {
+            tags.add_or_increment(tag);
+        }
+    }
+    tags
+}
+
+#[test]
+fn conditional_probability_is_asymmetric() {
+    assert!(!ConditionalProbability.is_symmetric());
+    assert_close(ConditionalProbability.score(ATTRACTED), 0.8);
+    assert_close(ConditionalProbability.score(swapped(ATTRACTED)), 40.0 / 60.0);
+
+    let tags = tags(&[("a", 50), ("b", 60)]);
+    let co_counts = SymmetricCSR::from_triples(&[(0, 1, 40)], 2);
+    let matrix = get_association_matrix(100, &tags, &co_counts, &ConditionalProbability).unwrap();
+    assert_close(matrix.value(0, 1).unwrap(), 0.8);
+    assert_close(matrix.value(1, 0).unwrap(), 40.0 / 60.0);
+
+    let symmetric = get_association_matrix(100, &tags, &co_counts, &Jaccard).unwrap();
+    assert_eq!(symmetric.value(0, 1), symmetric.value(1, 0));
+}
+
+#[test]
+fn measures_dispatch_to_their_unit_structs() {
+    let measures: [(Measure, &dyn AssociationMeasure); 9] = [
+        (Measure::Npmi, &Npmi),
+        (Measure::Pmi, &Pmi),
+        (Measure::Ppmi, &Ppmi),
+        (Measure::Jaccard, &Jaccard),
+        (Measure::Dice, &Dice),
+        (Measure::Lift, &Lift),
+        (Measure::ConditionalProbability, &ConditionalProbability),
+        (Measure::LogLikelihoodRatio, &LogLikelihoodRatio),
+        (Measure::ChiSquare, &ChiSquare),
+    ];
+    for (measure, unit) in measures {
+        assert_eq!(measure.is_symmetric(), unit.is_symmetric(), "{measure:?}");
+        for counts in [INDEPENDENT, ATTRACTED, swapped(ATTRACTED)] {
+            assert_eq!(measure.score(counts).to_bits(), unit.score(counts).to_bits(), "{measure:?}");
+        }
+    }
+}
+
+#[test]
+fn too_many_posts_are_an_error() {
+    let tags = Tags::new();
+    let co_counts = SymmetricCSR::new(0);
+    assert!(matches!(
+        get_association_matrix(u32::MAX as usize + 1, &tags, &co_counts, &Npmi),
+        Err(Error::TooManyPosts(_))
+    ));
+}
+
+#[test]
+fn tags_must_match_the_co_counts() {
+    let tags = tags(&[("a", 1), ("b", 1)]);
+    let co_counts = SymmetricCSR::new(3);
+    assert!(matches!(
+        get_association_matrix(2, &tags, &co_counts, &Npmi),
+        Err(Error::Matrix(csr_matrix::Error::DimensionMismatch { .. }))
+    ));
+}
+
+/// The posts `a b`, `a c`, `b d` and `c d`: `a` and `d` never appear
+/// together.
+#[test]
+fn pairs_that_never_co_occur_score_minus_one() {
+    let tags = tags(&[("a", 2), ("b", 2), ("c", 2), ("d", 2)]);
+    let co_counts = get_co_count_matrix(4, &tags, vec![vec![0, 1], vec![0, 2], vec![1, 3], vec![2, 3]]).unwrap();
+
+    assert_eq!(get_npmi("a", "d", 4, &co_counts, &tags).unwrap(), -1.0);
+    let together = PairCounts {
+        c_ab: 1,
+        c_a: 2,
+        c_b: 2,
+        n_posts: 4,
+    };
+    assert_eq!(get_npmi("a", "b", 4, &co_counts, &tags).unwrap(), Npmi.score(together));
+    assert_eq!(
+        get_npmis(&[("a", "d"), ("a", "z")], 4, &co_counts, &tags).unwrap(),
+        vec![Some(-1.0), None]
+    );
+    assert!(matches!(get_npmi("a", "z", 4, &co_counts, &tags), Err(Error::UnknownTag(_))));
+    assert!(matches!(
+        get_npmi("a", "d", u32::MAX as usize + 1, &co_counts, &tags),
+        Err(Error::TooManyPosts(_))
+    ));
+}
+
+/// 100 posts: `a` on 50 and `b` on 40, 30 of them together. `odd` is on a
+/// post with `a` and `rare`, and two with `b`.
+fn vocabulary() -> (Tags, CoCountMatrix) {
+    let tags = tags(&[("a", 50), ("b", 40), ("rare", 1), ("odd", 3)]);
+    let co_counts = SymmetricCSR::from_triples(&[(0, 1, 30), (0, 2, 1), (0, 3, 1), (1, 3, 2), (2, 3, 1)], 4);
+    (tags, co_counts)
+}
+
+#[test]
+fn default_smoothing_is_plain_npmi() {
+    let (tags, co_counts) = vocabulary();
+    let smoothed = get_smoothed_npmi_matrix(100, &tags, &co_counts, Smoothing::default()).unwrap();
+    let plain = get_npmi_matrix(100, &tags, &co_counts).unwrap();
+    let bits = |matrix: &prompt_get::nmpi::AssociationMatrix| {
+        matrix.to_csr().iter().map(|(row, col, score)| (row, col, score.to_bits())).collect::<Vec<_>>()
+    };
+    assert_eq!(bits(&smoothed), bits(&plain));
+
+    let measure = SmoothedNpmi::new(&tags, Smoothing::default());
+    assert!(measure.is_symmetric());
+    for counts in [INDEPENDENT, ATTRACTED, swapped(ATTRACTED)] {
+        assert_eq!(measure.score(counts).to_bits(), Npmi.score(counts).to_bits());
+    }
+}
+
+#[test]
+fn additive_smoothing_discounts_singleton_pairs() {
+    let singleton = PairCounts {
+        c_ab: 1,
+        c_a: 1,
+        c_b: 1,
+        n_posts: 100,
+    };
+    assert_eq!(Npmi.score(singleton), 1.0);
+
+    let (tags, _) = vocabulary();
+    let smoothing = Smoothing {
+        additive: 1.0,
+        ..Default::default()
+    };
+    let score = SmoothedNpmi::new(&tags, smoothing).score(singleton);
+    assert!(0.0 < score && score < 1.0, "{score}");
+}
+
+#[test]
+fn support_thresholds_zero_out_rare_pairs() {
+    let (tags, co_counts) = vocabulary();
+    let stored = |smoothing| {
+        let matrix = get_smoothed_npmi_matrix(100, &tags, &co_counts, smoothing).unwrap();
+        matrix
+            .to_csr()
+            .iter()
+            .filter(|&(row, col, score)| row < col && score > 0.0)
+            .map(|(row, col, _)| (row, col))
+            .collect::<Vec<_>>()
+    };
+
+    // `a` and `odd` co-occur less than chance
+    assert_eq!(stored(Smoothing::default()), [(0, 1), (0, 2), (1, 3), (2, 3)]);
+    let min_tag_count = Smoothing {
+        min_tag_count: 2,
+        ..Default::default()
+    };
+    assert_eq!(stored(min_tag_count), [(0, 1), (1, 3)]);
+    let min_pair_count = Smoothing {
+        min_pair_count: 3,
+        ..Default::default()
+    };
+    assert_eq!(stored(min_pair_count), [(0, 1)]);
+    let both = Smoothing {
+        min_tag_count: 41,
+        min_pair_count: 2,
+        ..Default::default()
+    };
+    assert_eq!(stored(both), []);
+}
+
+#[test]
+fn context_smoothing_keeps_scores_in_range() {
+    let (tags, co_counts) = vocabulary();
+    let smoothing = Smoothing {
+        alpha: 0.75,
+        ..Default::default()
+    };
+    assert!(!SmoothedNpmi::new(&tags, smoothing).is_symmetric());
+
+    let matrix = get_smoothed_npmi_matrix(100, &tags, &co_counts, smoothing).unwrap();
+    assert!(matrix.to_csr().iter().all(|(_, _, score)| (0.0..=1.0).contains(&score)));
+    // only the column tag is smoothed, raising the probability of `rare`
+    // and lowering that of `a`
+    assert!(matrix.value(0, 2).unwrap() < matrix.value(2, 0).unwrap());
+
+    // a pair always together, whose column tag is frequent enough for its
+    // smoothed probability to drop below theirs, would score above 1
+    let together = PairCounts {
+        c_ab: 40,
+        c_a: 40,
+        c_b: 40,
+        n_posts: 100,
+    };
+    assert_eq!(SmoothedNpmi::new(&tags, smoothing).score(together), 1.0);
+}
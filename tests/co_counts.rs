@@ -0,0 +1,75 @@
+use csr_matrix::SymmetricCSR;
+use prompt_get::nmpi::{get_co_count_matrix, CoCountMatrix};
+use prompt_get::tags::Tags;
+use rustc_hash::FxHashMap;
+
+#[test]
+fn no_posts_give_an_empty_matrix() {
+    let mut tags = Tags::new();
+    tags.add_or_increment("cat");
+
+    let co_counts = get_co_count_matrix(0, &tags, Vec::new()).unwrap();
+
+    assert_eq!((co_counts.n_rows(), co_counts.n_cols()), (1, 1));
+    assert_eq!(co_counts.n_stored(), 0);
+}
+
+#[test]
+fn tags_can_be_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Tags>();
+}
+
+/// `n_posts` posts of up to 8 distinct tags out of `n_tags`, drawn with a
+/// fixed linear congruential generator.
+fn posts(n_posts: usize, n_tags: u32) -> Vec<Vec<u32>> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (state >> 33) as u32
+    };
+    (0..n_posts)
+        .map(|_| {
+            let mut post: Vec<u32> = (0..next() % 9).map(|_| next() % n_tags).collect();
+            post.sort_unstable();
+            post.dedup();
+            post
+        })
+        .collect()
+}
+
+/// Co-counts summed one post at a time on the calling thread.
+fn single_threaded_co_counts(n_tags: usize, posts: &[Vec<u32>]) -> CoCountMatrix {
+    let mut counts = FxHashMap::default();
+    for post in posts {
+        for (i, &a) in post.iter().enumerate() {
+            for &b in &post[i + 1..] {
+                *counts.entry((a.min(b) as usize, a.max(b) as usize)).or_insert(0) += 1;
+            }
+        }
+    }
+    SymmetricCSR::from_fxhash(&counts, n_tags)
+}
+
+#[test]
+fn threaded_co_counts_match_single_threaded_ones() {
+    const N_TAGS: u32 = 40;
+    let mut tags = Tags::new();
+    for idx in 0..N_TAGS {
+        tags.add_or_increment(&format!("tag_{idx}"));
+    }
+
+    // fewer posts than workers, then many posts per worker
+    for n_posts in [5, 2_000] {
+        let posts = posts(n_posts, N_TAGS);
+        let expected = single_threaded_co_counts(tags.len(), &posts);
+
+        let co_counts = get_co_count_matrix(n_posts, &tags, posts).unwrap();
+
+        assert_eq!(
+            co_counts.iter_upper().collect::<Vec<_>>(),
+            expected.iter_upper().collect::<Vec<_>>(),
+            "{n_posts} posts"
+        );
+    }
+}
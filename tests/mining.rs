@@ -0,0 +1,82 @@
+//! Implications and aliases mined from co-counts, and the CSV they are
+//! written to for review.
+
+use prompt_get::nmpi::{get_co_count_matrix, mine_aliases, mine_implications, MiningParams, Suggestion};
+use prompt_get::read_write::{read_aliases, read_implications, read_posts, write_suggestions};
+use prompt_get::tags::Tags;
+use std::fs;
+
+const PARAMS: MiningParams = MiningParams {
+    min_support: 2,
+    min_confidence: 0.9,
+    min_similarity: 0.99,
+};
+
+fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("prompt_get_{}_{name}", std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+/// `a` is only ever tagged with `b`, which is also tagged alone. `x` and
+/// `y` are never tagged together but each with `n1` and `n2` equally often.
+/// Tests run in parallel, so each writes the posts under its own `test`
+/// name.
+fn mined(test: &str) -> (Tags, Vec<Suggestion>, Vec<Suggestion>) {
+    let path = temp_path(&format!("{test}_posts.csv"));
+    let posts = ["a b", "a b", "a b", "b", "b", "x n1", "x n2", "y n1", "y n2"];
+    let csv: String = posts.iter().enumerate().map(|(id, post)| format!("{id},{post}\n")).collect();
+    fs::write(&path, format!("id,tag_string\n{csv}")).unwrap();
+    let (n_posts, tags, posts_tag_idxs) = read_posts(&path).unwrap();
+    fs::remove_file(path).unwrap();
+
+    let co_counts = get_co_count_matrix(n_posts, &tags, posts_tag_idxs).unwrap();
+    let implications = mine_implications(&tags, &co_counts, PARAMS).unwrap();
+    let aliases = mine_aliases(&tags, &co_counts, PARAMS).unwrap();
+    (tags, implications, aliases)
+}
+
+fn names<'a>(tags: &'a Tags, suggestion: &Suggestion) -> (&'a str, &'a str) {
+    (
+        tags.get_name(suggestion.antecedent as usize).unwrap(),
+        tags.get_name(suggestion.consequent as usize).unwrap(),
+    )
+}
+
+#[test]
+fn a_tag_always_with_another_implies_it() {
+    let (tags, implications, _) = mined("a_tag_always_with_another_implies_it");
+
+    assert_eq!(implications.len(), 1);
+    assert_eq!(names(&tags, &implications[0]), ("a", "b"));
+    assert_eq!((implications[0].score, implications[0].support), (1.0, 3));
+}
+
+#[test]
+fn tags_with_identical_neighbours_are_aliases() {
+    let (tags, _, aliases) = mined("tags_with_identical_neighbours_are_aliases");
+
+    let pairs: Vec<_> = aliases.iter().map(|suggestion| names(&tags, suggestion)).collect();
+    assert!(pairs.contains(&("x", "y")), "{pairs:?}");
+    assert!(!pairs.iter().any(|&(antecedent, consequent)| [antecedent, consequent].contains(&"a")));
+    let x_y = aliases[pairs.iter().position(|&pair| pair == ("x", "y")).unwrap()];
+    assert!((x_y.score - 1.0).abs() < 1e-6);
+    assert_eq!(x_y.support, 0);
+}
+
+#[test]
+fn suggestions_csv_reads_back_as_implications_and_aliases() {
+    let (tags, implications, aliases) = mined("suggestions_csv_reads_back_as_implications_and_aliases");
+    let path = temp_path("suggestions.csv");
+    write_suggestions(&path, &tags, &implications, &aliases).unwrap();
+
+    let read = read_implications(&path).unwrap();
+    assert_eq!(read.len(), implications.len());
+    assert!(read.implies("a", "b"));
+    let read = read_aliases(&path).unwrap();
+    assert_eq!(read.len(), aliases.len());
+    for suggestion in &aliases {
+        let (antecedent, consequent) = names(&tags, suggestion);
+        assert_eq!(read.resolve(antecedent), read.resolve(consequent));
+    }
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,393 @@
+//! Model files: round trips, integrity checks and migration of legacy files.
+
+use csr_matrix::{SymmetricCSR, CSR};
+use prompt_get::association::{Measure, Smoothing};
+use prompt_get::error::Error;
+use prompt_get::nmpi::{AssociationMatrix, AssociationView};
+use prompt_get::read_write::{
+    migrate_data, read_data, write_association_mtx, write_association_npz, write_co_count_mtx, write_data, write_model,
+    BuildParams, MappedModel, ModelError, ModelHeader, DATA_VERSION,
+};
+use prompt_get::tags::{Aliases, Tags};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const PARAMS: BuildParams = BuildParams {
+    measure: Measure::Npmi,
+    smoothing: Smoothing {
+        min_tag_count: 0,
+        min_pair_count: 2,
+        additive: 1.0,
+        alpha: 0.75,
+    },
+    min_score: Some(0.05),
+    top_k: Some(200),
+};
+
+/// A path in the temp directory unique to this test process.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("prompt_get_{}_{name}", std::process::id()))
+}
+
+fn model() -> (Tags, AssociationMatrix) {
+    let mut tags = Tags::new();
+    for tag in ["cat", "feline", "cat", "dog", "artist:someone"] {
+        tags.add_or_increment(tag);
+    }
+    let npmi = SymmetricCSR::from_triples(&[(0, 1, 0.75), (2, 3, 0.25)], 4);
+    (tags, AssociationMatrix::Symmetric(npmi))
+}
+
+/// Compares scores only, so a symmetric matrix is the same as its full
+/// expansion.
+fn assert_same_model(tags: &Tags, npmi: &AssociationMatrix, expected: &(Tags, AssociationMatrix)) {
+    assert_eq!(tags.len(), expected.0.len());
+    for idx in 0..tags.len() {
+        assert_eq!(tags.get_name(idx), expected.0.get_name(idx));
+        assert_eq!(tags.get_count_idx(idx), expected.0.get_count_idx(idx));
+    }
+    assert_eq!(npmi.to_csr().iter().collect::<Vec<_>>(), expected.1.to_csr().iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn data_round_trips() {
+    let path = temp_path("data_round_trips.model");
+    let expected = model();
+    write_data(path.to_str().unwrap(), PARAMS, 3, &expected.0, &expected.1).unwrap();
+
+    let (header, tags, npmi) = read_data(path.to_str().unwrap()).unwrap();
+    assert_eq!((header.params, header.n_posts), (PARAMS, 3));
+    assert_same_model(&tags, &npmi, &expected);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mapped_model_round_trips() {
+    let path = temp_path("mapped_model_round_trips.model");
+    let expected = model();
+    write_model(path.to_str().unwrap(), PARAMS, 3, &expected.0, &expected.1).unwrap();
+    assert!(!temp_path("mapped_model_round_trips.model.tmp").exists());
+
+    let model = MappedModel::open(path.to_str().unwrap()).unwrap();
+    model.verify().unwrap();
+    assert_eq!((model.header().params, model.n_posts()), (PARAMS, 3));
+    assert_same_model(model.tags(), &model.npmi_matrix().to_matrix(), &expected);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mapped_model_checks_its_checksums() {
+    let path = temp_path("mapped_model_checks_its_checksums.model");
+    let (tags, npmi) = model();
+    write_model(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    let written = fs::read(&path).unwrap();
+    let flipped = |at: usize| {
+        let mut bytes = written.clone();
+        bytes[at] ^= 1;
+        fs::write(&path, bytes).unwrap();
+    };
+
+    // the matrix is only checked on demand
+    flipped(written.len() - 1);
+    let model = MappedModel::open(path.to_str().unwrap()).unwrap();
+    assert!(matches!(
+        model.verify(),
+        Err(Error::Model(ModelError::ChecksumMismatch { .. }))
+    ));
+    drop(model);
+
+    // the tags follow the header and their u64 length
+    let body_at = 16 + u32::from_le_bytes(written[12..16].try_into().unwrap()) as usize;
+    flipped(body_at + 8);
+    assert!(matches!(
+        MappedModel::open(path.to_str().unwrap()),
+        Err(Error::Model(ModelError::ChecksumMismatch { .. }))
+    ));
+    fs::remove_file(path).unwrap();
+}
+
+#[derive(Serialize)]
+struct LegacyTagData<'a> {
+    name: &'a str,
+    count: u32,
+}
+
+/// Tags as `write_data` encoded them before the format was versioned.
+#[derive(Serialize)]
+struct LegacyTags<'a> {
+    vec: Vec<LegacyTagData<'a>>,
+    tag_set: HashMap<&'a str, usize>,
+}
+
+/// What `write_data` wrote before the format was versioned.
+#[derive(Serialize)]
+struct LegacyData<'a> {
+    n_posts: usize,
+    tags: LegacyTags<'a>,
+    csr: CSR<f32, u32>,
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serde::encode_to_vec(value, bincode::config::standard()).unwrap()
+}
+
+/// A file as `write_data` lays it out, of format `version`.
+fn data_file(version: u32, header: &[u8], payload: &[u8]) -> Vec<u8> {
+    [
+        &b"PGNPMI\0\0"[..],
+        &version.to_le_bytes(),
+        &(header.len() as u32).to_le_bytes(),
+        header,
+        payload,
+    ]
+    .concat()
+}
+
+#[test]
+fn legacy_data_files_are_migrated() {
+    let path = temp_path("legacy_data_files_are_migrated.data");
+    let expected = model();
+    let names: Vec<&str> = (0..expected.0.len()).map(|idx| expected.0.get_name(idx).unwrap()).collect();
+    let legacy = LegacyData {
+        n_posts: 3,
+        tags: LegacyTags {
+            vec: names
+                .iter()
+                .enumerate()
+                .map(|(idx, &name)| LegacyTagData {
+                    name,
+                    count: expected.0.get_count_idx(idx).unwrap(),
+                })
+                .collect(),
+            tag_set: names.iter().enumerate().map(|(idx, &name)| (name, idx)).collect(),
+        },
+        csr: expected.1.to_csr(),
+    };
+    fs::write(&path, encode(&legacy)).unwrap();
+
+    assert!(matches!(
+        read_data(path.to_str().unwrap()),
+        Err(Error::Model(ModelError::Legacy))
+    ));
+    migrate_data(path.to_str().unwrap(), PARAMS).unwrap();
+    let (header, tags, npmi) = read_data(path.to_str().unwrap()).unwrap();
+    assert_eq!((header.params, header.n_posts, header.symmetric), (PARAMS, 3, false));
+    assert_same_model(&tags, &npmi, &expected);
+
+    // versioned files are left as they are
+    let migrated = fs::read(&path).unwrap();
+    migrate_data(path.to_str().unwrap(), PARAMS).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), migrated);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn other_versions_are_unsupported() {
+    let (tags, npmi) = model();
+    let with_version = |path: &PathBuf, version: u32| {
+        let mut bytes = fs::read(path).unwrap();
+        bytes[8..12].copy_from_slice(&version.to_le_bytes());
+        fs::write(path, bytes).unwrap();
+    };
+
+    let path = temp_path("other_versions_are_unsupported.data");
+    write_data(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    with_version(&path, DATA_VERSION + 1);
+    assert!(matches!(
+        read_data(path.to_str().unwrap()),
+        Err(Error::Model(ModelError::UnsupportedVersion(v))) if v == DATA_VERSION + 1
+    ));
+    fs::remove_file(path).unwrap();
+
+    let path = temp_path("other_versions_are_unsupported.model");
+    write_model(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    with_version(&path, 0);
+    assert!(matches!(
+        MappedModel::open(path.to_str().unwrap()),
+        Err(Error::Model(ModelError::UnsupportedVersion(0)))
+    ));
+    fs::remove_file(path).unwrap();
+}
+
+fn with_alias(mut tags: Tags) -> Tags {
+    let mut aliases = Aliases::new();
+    aliases.insert("kitty", "cat");
+    tags.set_aliases(&aliases);
+    tags
+}
+
+#[test]
+fn aliases_survive_both_formats() {
+    let (tags, npmi) = model();
+    let tags = with_alias(tags);
+
+    let path = temp_path("aliases_survive_both_formats.data");
+    write_data(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    let (_, read, _) = read_data(path.to_str().unwrap()).unwrap();
+    assert_eq!(read.get_idx("kitty"), Some(0));
+    fs::remove_file(path).unwrap();
+
+    let path = temp_path("aliases_survive_both_formats.model");
+    write_model(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    let model = MappedModel::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(model.tags().get_idx("kitty"), Some(0));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn symmetric_matrices_store_each_pair_once() {
+    let (tags, npmi) = model();
+    let full = AssociationMatrix::Full(npmi.to_csr());
+    let size = |name: &str, npmi: &AssociationMatrix| {
+        let path = temp_path(name);
+        write_data(path.to_str().unwrap(), PARAMS, 3, &tags, npmi).unwrap();
+        let data = fs::metadata(&path).unwrap().len();
+        let (header, _, read) = read_data(path.to_str().unwrap()).unwrap();
+        assert_eq!((header.n_nz, header.symmetric), (npmi.n_stored() as u64, matches!(read, AssociationMatrix::Symmetric(_))));
+        fs::remove_file(&path).unwrap();
+        data
+    };
+    assert_eq!((npmi.n_stored(), full.n_stored()), (2, 4));
+    assert!(size("symmetric.data", &npmi) < size("full.data", &full));
+
+    let path = temp_path("symmetric_matrices_store_each_pair_once.model");
+    write_model(path.to_str().unwrap(), PARAMS, 3, &tags, &npmi).unwrap();
+    let model = MappedModel::open(path.to_str().unwrap()).unwrap();
+    let view = model.npmi_matrix();
+    assert!(matches!(view, AssociationView::Symmetric(_)));
+    assert_eq!(view.n_stored(), 2);
+    assert_eq!(view.inter_row(1).collect::<Vec<_>>(), [(0, 0.75)]);
+    assert_eq!(view.inter_row(3).collect::<Vec<_>>(), [(2, 0.25)]);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn asymmetric_matrices_round_trip_in_full() {
+    let (tags, _) = model();
+    let expected = (tags, AssociationMatrix::Full(CSR::from_triples(&[(0, 1, 0.75), (1, 0, 0.5), (2, 3, 0.25)], 4, 4)));
+
+    let path = temp_path("asymmetric_matrices_round_trip_in_full.data");
+    write_data(path.to_str().unwrap(), PARAMS, 3, &expected.0, &expected.1).unwrap();
+    let (header, tags, npmi) = read_data(path.to_str().unwrap()).unwrap();
+    assert!(!header.symmetric);
+    assert_same_model(&tags, &npmi, &expected);
+    fs::remove_file(path).unwrap();
+
+    let path = temp_path("asymmetric_matrices_round_trip_in_full.model");
+    write_model(path.to_str().unwrap(), PARAMS, 3, &expected.0, &expected.1).unwrap();
+    let model = MappedModel::open(path.to_str().unwrap()).unwrap();
+    assert!(matches!(model.npmi_matrix(), AssociationView::Full(_)));
+    assert_same_model(model.tags(), &model.npmi_matrix().to_matrix(), &expected);
+    fs::remove_file(path).unwrap();
+}
+
+/// A matrix encoded as `CSR` encodes it, with arrays that need not agree.
+#[derive(Clone, Serialize)]
+struct RawCSR {
+    n_rows: usize,
+    n_cols: usize,
+    n_nz: usize,
+    row_ptr: Vec<u32>,
+    col_idx: Vec<u32>,
+    val: Vec<f32>,
+}
+
+/// A matrix encoded as `AssociationMatrix` encodes it.
+#[derive(Serialize)]
+enum RawMatrix {
+    Symmetric(RawCSR),
+    Full(RawCSR),
+}
+
+#[test]
+fn inconsistent_matrices_are_corrupt() {
+    let path = temp_path("inconsistent_matrices_are_corrupt.data");
+    let (tags, _) = model();
+    // the checksum is right, but the row pointers run past the one entry
+    let raw = RawCSR {
+        n_rows: 4,
+        n_cols: 4,
+        n_nz: 1,
+        row_ptr: vec![0, 5, 5, 5, 5],
+        col_idx: vec![1],
+        val: vec![0.5],
+    };
+    for (matrix, symmetric) in [(RawMatrix::Symmetric(raw.clone()), true), (RawMatrix::Full(raw), false)] {
+        let payload = [encode(&tags), encode(&matrix)].concat();
+        let header = ModelHeader {
+            params: PARAMS,
+            n_posts: 3,
+            n_tags: tags.len() as u64,
+            n_nz: 1,
+            symmetric,
+            tags_checksum: crc32fast::hash(&encode(&tags)),
+            checksum: crc32fast::hash(&payload),
+        };
+        fs::write(&path, data_file(DATA_VERSION, &encode(&header), &payload)).unwrap();
+
+        assert!(matches!(
+            read_data(path.to_str().unwrap()),
+            Err(Error::Model(ModelError::Corrupt(_)))
+        ));
+    }
+    fs::remove_file(path).unwrap();
+}
+
+/// Lines of the `.tags` file written next to `path`, which it removes along
+/// with `path`.
+fn take_tag_names(path: &Path) -> Vec<String> {
+    let names_path = path.with_extension("tags");
+    let names = fs::read_to_string(&names_path).unwrap().lines().map(str::to_string).collect();
+    fs::remove_file(names_path).unwrap();
+    fs::remove_file(path).unwrap();
+    names
+}
+
+/// Checks that line `i` of an exported `.tags` file names row and column `i`
+/// of the exported `matrix`, as they are in `tags` and `expected`.
+fn assert_exported(names: &[String], matrix: &CSR<f32, u32>, tags: &Tags, expected: &CSR<f32, u32>) {
+    assert_eq!(names.len(), tags.len());
+    assert_eq!((matrix.n_rows, matrix.n_cols), (names.len(), names.len()));
+    for (idx, name) in names.iter().enumerate() {
+        assert_eq!(tags.get_idx(name), Some(idx));
+    }
+    assert_eq!(matrix.iter().collect::<Vec<_>>(), expected.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn mtx_exports_round_trip_with_their_tag_names() {
+    let (tags, npmi) = model();
+    let full = AssociationMatrix::Full(CSR::from_triples(&[(0, 1, 0.75), (1, 0, 0.5), (2, 3, 0.25)], 4, 4));
+
+    for (name, matrix) in [("symmetric", &npmi), ("full", &full)] {
+        let path = temp_path(&format!("mtx_exports_round_trip_{name}.mtx"));
+        write_association_mtx(path.to_str().unwrap(), &tags, matrix).unwrap();
+        let read: CSR<f32, u32> = CSR::read_mtx(BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+        assert_exported(&take_tag_names(&path), &read, &tags, &matrix.to_csr());
+    }
+
+    let co_counts = SymmetricCSR::<u32, u32>::from_triples(&[(0, 1, 2), (2, 3, 1)], 4);
+    let path = temp_path("mtx_exports_round_trip_co_counts.mtx");
+    write_co_count_mtx(path.to_str().unwrap(), &tags, &co_counts).unwrap();
+    let read: SymmetricCSR<u32, u32> = SymmetricCSR::read_mtx(BufReader::new(fs::File::open(&path).unwrap())).unwrap();
+    let names = take_tag_names(&path);
+    assert_eq!(names, ["cat", "feline", "dog", "artist:someone"]);
+    assert_eq!(read.to_csr().iter().collect::<Vec<_>>(), co_counts.to_csr().iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn npz_exports_round_trip_with_their_tag_names() {
+    let (tags, npmi) = model();
+    let full = AssociationMatrix::Full(CSR::from_triples(&[(0, 1, 0.75), (1, 0, 0.5), (2, 3, 0.25)], 4, 4));
+
+    // both are written in full, so `load_npz` needs no special casing
+    for (name, matrix) in [("symmetric", &npmi), ("full", &full)] {
+        let path = temp_path(&format!("npz_exports_round_trip_{name}.npz"));
+        write_association_npz(path.to_str().unwrap(), &tags, matrix).unwrap();
+        let read: CSR<f32, u32> = CSR::read_npz(fs::File::open(&path).unwrap()).unwrap();
+        assert_exported(&take_tag_names(&path), &read, &tags, &matrix.to_csr());
+    }
+}
@@ -0,0 +1,228 @@
+//! Tag metadata: categories, aliases and implications.
+
+use csr_matrix::CSR;
+use prompt_get::error::Error;
+use prompt_get::nmpi::{get_most_related_tags, AssociationMatrix};
+use prompt_get::read_write::{read_aliases, read_implications, read_posts_with, read_tag_categories, ImpliedTags, PostsOptions};
+use prompt_get::tags::{Aliases, Category, Implications, Tags};
+use std::fs;
+
+fn temp_csv(name: &str, contents: &str) -> String {
+    let path = std::env::temp_dir().join(format!("prompt_get_{}_{name}.csv", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn tags_missing_from_metadata_are_unknown() {
+    let path = temp_csv(
+        "categories",
+        "name,category\nseed,general\ncat,general\nsomeone,1\nbad,not_a_category\n",
+    );
+    let categories = read_tag_categories(&path).unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!(categories.len(), 3);
+
+    let mut tags = Tags::new();
+    for tag in ["seed", "cat", "someone", "bad", "dog"] {
+        tags.add_or_increment(tag);
+    }
+    tags.set_categories(&categories);
+    let found: Vec<Category> = (0..tags.len()).map(|idx| tags.get_category(idx).unwrap()).collect();
+    assert_eq!(
+        found,
+        [Category::General, Category::General, Category::Artist, Category::Unknown, Category::Unknown]
+    );
+
+    let npmi = AssociationMatrix::Full(CSR::from_triples(&[(0, 1, 0.5), (0, 2, 0.4), (0, 3, 0.3), (0, 4, 0.2)], 5, 5));
+    let related = |categories: Option<&[Category]>| {
+        get_most_related_tags(10, npmi.view(), &tags, vec![0], categories)
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(related(None), [1, 2, 3, 4]);
+    assert_eq!(related(Some(&[Category::General, Category::Artist])), [1, 2]);
+    assert_eq!(related(Some(&[Category::Unknown])), [3, 4]);
+}
+
+#[test]
+fn implied_tags_are_kept_expanded_or_stripped() {
+    let posts = temp_csv("implied_posts", "id,tag_string\n1,cat\n2,cat feline mammal\n3,kitty mammal\n");
+    let implications = temp_csv(
+        "implications",
+        "antecedent_name,consequent_name,status\nkitty,feline,active\nfeline,mammal,active\ncat,dog,deleted\n",
+    );
+    let mut options = PostsOptions {
+        implications: read_implications(&implications).unwrap(),
+        ..Default::default()
+    };
+    options.aliases.insert("kitty", "cat");
+    fs::remove_file(implications).unwrap();
+
+    let mut read = |implied| {
+        options.implied = implied;
+        let (_, tags, posts_tag_idxs) = read_posts_with(&posts, &options).unwrap();
+        posts_tag_idxs
+            .iter()
+            .map(|post| {
+                let mut names: Vec<&str> = post.iter().map(|&idx| tags.get_name(idx as usize).unwrap()).collect();
+                names.sort_unstable();
+                names.join(" ")
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(read(ImpliedTags::Keep), ["cat", "cat feline mammal", "cat mammal"]);
+    // `kitty` implies `feline` as the alias of `cat`
+    assert_eq!(read(ImpliedTags::Expand), ["cat feline mammal"; 3]);
+    assert_eq!(read(ImpliedTags::Strip), ["cat"; 3]);
+    fs::remove_file(posts).unwrap();
+}
+
+#[test]
+fn implications_are_transitive() {
+    let mut implications = Implications::new();
+    implications.insert("cat", "feline");
+    implications.insert("feline", "mammal");
+    implications.insert("cat", "mammal");
+
+    assert_eq!(implications.implied("cat"), ["feline", "mammal"]);
+    assert_eq!(implications.implied("feline"), ["mammal"]);
+    assert!(implications.implied("mammal").is_empty());
+    assert!(implications.implies("cat", "mammal"));
+    assert!(!implications.implies("mammal", "cat"));
+    assert_eq!(implications.find_cycle(), None);
+}
+
+#[test]
+fn implication_cycles_are_found() {
+    let mut implications = Implications::new();
+    implications.insert("a", "b");
+    implications.insert("b", "c");
+    assert_eq!(implications.find_cycle(), None);
+    implications.insert("c", "a");
+    assert!(matches!(implications.find_cycle(), Some("a" | "b" | "c")));
+    assert_eq!(implications.implied("a"), ["b", "c", "a"]);
+
+    let path = temp_csv("cyclic_implications", "antecedent,consequent\na,b\nb,a\n");
+    assert!(matches!(read_implications(&path), Err(Error::ImplicationCycle(_))));
+    fs::remove_file(path).unwrap();
+
+    // `b` and `c` are the same tag, so `a` and `b` imply each other
+    let mut implications = Implications::new();
+    implications.insert("a", "b");
+    implications.insert("c", "a");
+    let mut aliases = Aliases::new();
+    aliases.insert("c", "b");
+    assert_eq!(implications.find_cycle(), None);
+    assert!(implications.resolve_aliases(&aliases).find_cycle().is_some());
+    let posts = temp_csv("cyclic_posts", "id,tag_string\n1,a\n");
+    let options = PostsOptions {
+        aliases,
+        implications,
+        ..Default::default()
+    };
+    assert!(matches!(read_posts_with(&posts, &options), Err(Error::ImplicationCycle(_))));
+    fs::remove_file(posts).unwrap();
+}
+
+#[test]
+fn alias_cycles_are_errors() {
+    let path = temp_csv(
+        "cyclic_aliases",
+        "antecedent_name,consequent_name,status\na,b,active\nb,c,active\nc,a,active\n",
+    );
+    assert!(matches!(read_aliases(&path), Err(Error::AliasCycle(_))));
+    fs::remove_file(path).unwrap();
+
+    // a deleted alias does not close the cycle
+    let path = temp_csv(
+        "deleted_cyclic_aliases",
+        "antecedent_name,consequent_name,status\na,b,active\nb,a,deleted\n",
+    );
+    assert_eq!(read_aliases(&path).unwrap().resolve("a"), "b");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn aliases_resolve_after_reading_posts() {
+    let aliases = temp_csv("resolved_aliases", "antecedent,consequent\nkitty,cat\nkitten,kitty\n");
+    let posts = temp_csv("aliased_posts", "id,tag_string\n1,kitty dog\n2,cat\n3,kitten\n");
+    let options = PostsOptions {
+        aliases: read_aliases(&aliases).unwrap(),
+        ..Default::default()
+    };
+    let (_, tags, _) = read_posts_with(&posts, &options).unwrap();
+    fs::remove_file(aliases).unwrap();
+    fs::remove_file(posts).unwrap();
+
+    let cat = tags.get_idx("cat").unwrap();
+    assert_eq!(tags.get_count_idx(cat), Some(3));
+    assert_eq!(tags.get_idx("kitty"), Some(cat));
+    assert_eq!(tags.get_idx("kitten"), Some(cat));
+    assert_eq!(tags.len(), 2);
+}
+
+/// `a` on 3 posts, `b` on 1, `c` on 2 and `d` on 5, with co-counts among
+/// `a`, `c` and `d` only.
+fn counted() -> (Tags, CSR<u32, u32>) {
+    let mut tags = Tags::new();
+    for (tag, count) in [("a", 3), ("b", 1), ("c", 2), ("d", 5)] {
+        for _ in 0..count {
+            tags.add_or_increment(tag);
+        }
+    }
+    let mut aliases = Aliases::new();
+    aliases.insert("alpha", "a");
+    aliases.insert("beta", "b");
+    tags.set_aliases(&aliases);
+
+    let co_counts = CSR::from_triples(&[(0, 2, 1), (2, 0, 1), (0, 3, 2), (3, 0, 2)], 4, 4);
+    (tags, co_counts)
+}
+
+fn names_and_counts(tags: &Tags) -> Vec<(&str, u32)> {
+    (0..tags.len())
+        .map(|idx| (tags.get_name(idx).unwrap(), tags.get_count_idx(idx).unwrap()))
+        .collect()
+}
+
+#[test]
+fn most_frequent_tags_come_first() {
+    let (tags, _) = counted();
+    assert_eq!(tags.most_frequent(2), [3, 0]);
+    assert_eq!(tags.most_frequent(10), [3, 0, 2, 1]);
+}
+
+#[test]
+fn selected_tags_match_the_submatrix() {
+    let (tags, co_counts) = counted();
+    let idxs = tags.most_frequent(2);
+    let selected = tags.select(&idxs);
+    let sub = co_counts.submatrix(&idxs, &idxs);
+
+    assert_eq!(names_and_counts(&selected), [("d", 5), ("a", 3)]);
+    assert_eq!(sub.n_rows, selected.len());
+    assert_eq!(sub.value(0, 1), co_counts.value(3, 0));
+    // aliases follow their tag, or go with it
+    assert_eq!(selected.get_idx("alpha"), Some(1));
+    assert_eq!(selected.get_idx("beta"), None);
+}
+
+#[test]
+fn remapped_tags_match_the_compacted_matrix() {
+    let (tags, co_counts) = counted();
+    let (compacted, map) = co_counts.compact_square();
+    let remapped = tags.remap(&map);
+
+    assert_eq!(map, [Some(0), None, Some(1), Some(2)]);
+    assert_eq!(names_and_counts(&remapped), [("a", 3), ("c", 2), ("d", 5)]);
+    assert_eq!(compacted.n_rows, remapped.len());
+    for (row, col, val) in &co_counts {
+        let (new_row, new_col) = (map[row].unwrap(), map[col].unwrap());
+        assert_eq!(compacted.value(new_row, new_col), Some(val));
+        assert_eq!(remapped.get_name(new_row), tags.get_name(row));
+        assert_eq!(remapped.get_name(new_col), tags.get_name(col));
+    }
+    assert_eq!(remapped.get_idx("alpha"), Some(0));
+}